@@ -0,0 +1,28 @@
+pub use anchor_lang::prelude::*;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+        constraint = marketplace.pending_admin == Some(pending_admin.key()) @ MarketplaceError::InvalidAdmin,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+}
+
+impl<'info> AcceptAdmin<'info> {
+    pub fn accept_admin(
+        &mut self,
+    ) -> Result<()> {
+
+        self.marketplace.admin = self.pending_admin.key();
+        self.marketplace.pending_admin = None;
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,27 +1,25 @@
-pub use anchor_lang::{
-    prelude::*,
-    system_program::{Transfer, transfer}
-};
+pub use anchor_lang::prelude::*;
 
-use mpl_token_metadata::types::{TransferArgs, UnlockArgs};
+use mpl_token_metadata::types::{TransferArgs, UnlockArgs, TokenStandard};
 pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
 
 
 use anchor_spl::{
-    token::{Mint, TokenAccount, CloseAccount, close_account}, 
+    token_interface::{Mint, TokenAccount, TokenInterface}, 
     metadata::{Metadata, MetadataAccount, MasterEditionAccount,
     mpl_token_metadata::instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs, UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs}}, 
     associated_token::AssociatedToken
 };
-pub use anchor_spl::token::Token;
 
 pub use crate::state::*;
 pub use crate::errors::*;
+use crate::utils::{creator_royalties, close_bid_vault_ata, amount_after_fees, pay_sale_in_sol, pay_sale_in_token, SolPayment, TokenPayment, release_listing_vault};
 
 #[derive(Accounts)]
 pub struct AcceptBid<'info> {
     #[account(mut)]
     pub lister: Signer<'info>,
+    #[account(mut)]
     /// CHECK: no need to check it out
     pub bidder: AccountInfo<'info>,
     #[account(
@@ -29,29 +27,45 @@ pub struct AcceptBid<'info> {
         payer = lister,
         associated_token::mint = nft,
         associated_token::authority = bidder,
+        associated_token::token_program = token_program,
     )]
-    pub bidder_ata: Account<'info, TokenAccount>,
+    pub bidder_ata: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = nft,
         associated_token::authority = lister,
+        associated_token::token_program = token_program,
     )]
-    pub lister_ata: Account<'info, TokenAccount>,
+    pub lister_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes(), marketplace.admin.key().as_ref()],
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
         bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
     #[account(
         mut, 
         close = lister,
-        seeds = [b"listing", marketplace.key().as_ref()],
+        seeds = [b"listing", marketplace.key().as_ref(), nft.key().as_ref()],
         bump,
         has_one = lister,
         has_one = nft,
     )]
     pub listing: Account<'info, Listing>,
+    // Only needed for listings escrowed in the listing vault
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = listing,
+        associated_token::token_program = token_program,
+    )]
+    pub listing_vault: Option<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         close = bidder,
@@ -61,100 +75,176 @@ pub struct AcceptBid<'info> {
     )]
     pub bid: Account<'info, BidState>,
     #[account(
-        seeds = [b"bidding_vault", bid.key().as_ref()],
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
         bump,
     )]
     pub bid_vault: SystemAccount<'info>,
 
     #[account(mut)]
-    pub nft: Account<'info, Mint>,
-    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
     pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
     pub edition: Account<'info, MasterEditionAccount>,
 
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = lister,
+        associated_token::mint = payment_mint,
+        associated_token::authority = lister,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub lister_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = lister,
+        associated_token::mint = payment_mint,
+        associated_token::authority = fee_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub fee_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
     #[account(address = INSTRUCTIONS_ID)]
     /// CHECK: no need to check it out
     pub sysvar_instruction: AccountInfo<'info>,
     pub token_metadata_program: Program<'info, Metadata>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The payment mint may live under a different token program than the NFT
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> AcceptBid<'info> {
     pub fn accept_bid(
         &mut self,
-        bumps: AcceptBidBumps
+        min_price: u64,
+        bumps: AcceptBidBumps,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
 
+        require!(!self.listing.is_expired(Clock::get()?.unix_timestamp), MarketplaceError::ListingExpired);
+        // The bidder can lower the bid right before it's accepted, so the seller says what they expect at least
+        require_gte!(self.bid.price, min_price, MarketplaceError::PriceBelowMin);
+
         let bid_key = self.bid.key();
         let seed = &[
-            b"bidding_vault",
+            b"bid_vault",
             bid_key.as_ref(),
-            &[bumps.bid]
+            &[bumps.bid_vault]
         ];
         let signer_seeds = &[&seed[..]];
 
-        // Close the listing vault (ATA) > We get the amount sent directly to the lister since it's Solana
-        let close_program = self.token_program.to_account_info();
-        let close_accounts = CloseAccount {
-            account: self.bid_vault.to_account_info(),
-            destination: self.lister.to_account_info(),
-            authority: self.bid.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(close_program, close_accounts, signer_seeds);
+        let fee = self.marketplace.fee_amount(self.bid.price);
+        let royalties = creator_royalties(&self.metadata, self.bid.price);
 
-        close_account(cpi_ctx)?;
+        match self.bid.payment_mint {
+            None => self.pay_in_sol(fee, &royalties, remaining_accounts, signer_seeds)?,
+            Some(payment_mint) => self.pay_in_token(payment_mint, fee, &royalties, remaining_accounts, signer_seeds)?,
+        }
         
-        // Unlock the NFT before transfering it
-        let unlock_program = &self.token_program.to_account_info();
-        let authority = &self.listing.to_account_info();
-        let token_owner = &self.lister.to_account_info();
-        let token = &self.lister_ata.to_account_info();
-        let mint = &self.nft.to_account_info();
-        let metadata = &self.metadata.to_account_info();
-        let edition = &self.edition.to_account_info();
-        let payer = &self.lister.to_account_info();
-        let system_program = &self.system_program.to_account_info();
-        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
-        let spl_token_program = &self.token_program.to_account_info();
-
-        let unlock_cpi = UnlockCpi::new(
-            unlock_program,
-            UnlockCpiAccounts {
-                authority,
-                token_owner: Some(token_owner),
-                token,
-                mint,
-                metadata,
-                edition: Some(edition),
-                token_record: None,
-                payer,
-                system_program,
-                sysvar_instructions,
-                spl_token_program: Some(spl_token_program),
-                authorization_rules_program: None,
-                authorization_rules: None,
-            },
-            UnlockInstructionArgs {
-                unlock_args: UnlockArgs::V1 {
-                    authorization_data: None,
-                },
-            }
-        );
-
         let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
         let seed = &[
             b"listing",
             marketplace_key.as_ref(),
+            nft_key.as_ref(),
             &[bumps.listing]
         ];
         let signer_seeds = &[&seed[..]];
 
-        unlock_cpi.invoke_signed(signer_seeds)?;
+        // An escrowed NFT is sent straight out of the listing vault
+        if self.listing.escrowed {
+            let Some(listing_vault) = self.listing_vault.as_ref() else {
+                return err!(MarketplaceError::MissingListingVault);
+            };
+            return release_listing_vault(
+                listing_vault,
+                &self.nft,
+                &self.bidder_ata.to_account_info(),
+                &self.listing.to_account_info(),
+                &self.lister.to_account_info(),
+                &self.token_program.to_account_info(),
+                signer_seeds,
+            );
+        }
+
+        // Unlock the NFT before transfering it > A pNFT listed with a Sale delegate was never locked
+        let is_programmable = self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible);
+
+        if !is_programmable {
+            let unlock_program = &self.token_metadata_program.to_account_info();
+            let authority = &self.listing.to_account_info();
+            let token_owner = &self.lister.to_account_info();
+            let token = &self.lister_ata.to_account_info();
+            let mint = &self.nft.to_account_info();
+            let metadata = &self.metadata.to_account_info();
+            let edition = &self.edition.to_account_info();
+            let payer = &self.lister.to_account_info();
+            let system_program = &self.system_program.to_account_info();
+            let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+            let spl_token_program = &self.token_program.to_account_info();
+
+            let unlock_cpi = UnlockCpi::new(
+                unlock_program,
+                UnlockCpiAccounts {
+                    authority,
+                    token_owner: Some(token_owner),
+                    token,
+                    mint,
+                    metadata,
+                    edition: Some(edition),
+                    token_record: None,
+                    payer,
+                    system_program,
+                    sysvar_instructions,
+                    spl_token_program: Some(spl_token_program),
+                    authorization_rules_program: None,
+                    authorization_rules: None,
+                },
+                UnlockInstructionArgs {
+                    unlock_args: UnlockArgs::V1 {
+                        authorization_data: None,
+                    },
+                }
+            );
+
+            unlock_cpi.invoke_signed(signer_seeds)?;
+        }
         
         // Transfer the NFT > Then we close the account of the delegation so we don't need to revoke that.
-        let transfer_program = self.token_program.to_account_info();
+        let transfer_program = self.token_metadata_program.to_account_info();
         let token = &self.lister_ata.to_account_info();
         let token_owner = &self.lister.to_account_info();
         let destination_token = &self.bidder_ata.to_account_info();
@@ -179,16 +269,16 @@ impl<'info> AcceptBid<'info> {
                 mint,
                 metadata,
                 edition: Some(edition),
-                token_record: None,
-                destination_token_record: None,
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
                 authority,
                 payer,
                 system_program,
                 sysvar_instructions,
                 spl_token_program,
                 spl_ata_program,
-                authorization_rules_program: None,
-                authorization_rules: None,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
             },
             TransferInstructionArgs {
                 transfer_args: TransferArgs::V1 {
@@ -202,4 +292,81 @@ impl<'info> AcceptBid<'info> {
         
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn pay_in_sol(
+        &self,
+        fee: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        // Empty the bid vault > What is left after the Fee and Royalties goes to the lister
+        let seller_amount = amount_after_fees(self.bid_vault.lamports(), fee, royalties)?;
+
+        // Pay the lister, the Fee and the creators' Royalties out of the bid vault
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.bid_vault.to_account_info(),
+                seller: self.lister.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            signer_seeds,
+        )
+    }
+
+    fn pay_in_token(
+        &self,
+        payment_mint: Pubkey,
+        fee: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        let (Some(mint), Some(bid_vault_payment_ata), Some(lister_payment_ata), Some(fee_vault_payment_ata), Some(token_program)) = (
+            self.payment_mint.as_ref(),
+            self.bid_vault_payment_ata.as_ref(),
+            self.lister_payment_ata.as_ref(),
+            self.fee_vault_payment_ata.as_ref(),
+            self.payment_token_program.as_ref(),
+        ) else {
+            return err!(MarketplaceError::MissingPaymentAccounts);
+        };
+        require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+        // Empty the bid vault > What is left after the Fee and Royalties goes to the lister
+        let seller_amount = amount_after_fees(bid_vault_payment_ata.amount, fee, royalties)?;
+
+        // Pay the lister, the Fee and the creators' Royalties out of the bid vault
+        pay_sale_in_token(
+            &TokenPayment {
+                mint,
+                from: bid_vault_payment_ata.to_account_info(),
+                authority: self.bid_vault.to_account_info(),
+                seller: lister_payment_ata.to_account_info(),
+                fee_vault: fee_vault_payment_ata.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            signer_seeds,
+        )?;
+
+        // Close the bid vault ATA and give the rent back to the bidder
+        close_bid_vault_ata(
+            &bid_vault_payment_ata.to_account_info(),
+            &self.bid_vault.to_account_info(),
+            &self.bidder.to_account_info(),
+            &token_program.to_account_info(),
+            signer_seeds,
+        )
+    }
+}
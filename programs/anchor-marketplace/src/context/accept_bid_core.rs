@@ -0,0 +1,265 @@
+pub use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    associated_token::AssociatedToken
+};
+use mpl_core::{
+    instructions::{
+        UpdatePluginV1Cpi, UpdatePluginV1CpiAccounts, UpdatePluginV1InstructionArgs,
+        TransferV1Cpi, TransferV1CpiAccounts, TransferV1InstructionArgs,
+    },
+    types::{FreezeDelegate, Plugin},
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{core_royalties, close_bid_vault_ata, amount_after_fees, pay_sale_in_sol, pay_sale_in_token, SolPayment, TokenPayment};
+
+#[derive(Accounts)]
+pub struct AcceptBidCore<'info> {
+    #[account(mut)]
+    pub lister: Signer<'info>,
+    #[account(mut)]
+    /// CHECK: no need to check it out
+    pub bidder: AccountInfo<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), asset.key().as_ref()],
+        bump,
+        has_one = lister,
+        has_one = collection,
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [b"bid", listing.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        has_one = bidder,
+    )]
+    pub bid: Account<'info, BidState>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    #[account(mut, owner = mpl_core::ID)]
+    /// CHECK: checked by the listing seeds
+    pub asset: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the listing
+    pub collection: AccountInfo<'info>,
+
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = lister,
+        associated_token::mint = payment_mint,
+        associated_token::authority = lister,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub lister_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = lister,
+        associated_token::mint = payment_mint,
+        associated_token::authority = fee_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub fee_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: checked by address
+    pub core_program: AccountInfo<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptBidCore<'info> {
+    pub fn accept_bid_core(
+        &mut self,
+        min_price: u64,
+        bumps: AcceptBidCoreBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        require!(!self.listing.is_expired(Clock::get()?.unix_timestamp), MarketplaceError::ListingExpired);
+        require_gte!(self.bid.price, min_price, MarketplaceError::PriceBelowMin);
+
+        let bid_key = self.bid.key();
+        let seed = &[
+            b"bid_vault",
+            bid_key.as_ref(),
+            &[bumps.bid_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let fee = self.marketplace.fee_amount(self.bid.price);
+        let royalties = core_royalties(&self.asset, &self.collection, self.bid.price);
+
+        match self.bid.payment_mint {
+            None => self.pay_in_sol(fee, &royalties, remaining_accounts, signer_seeds)?,
+            Some(payment_mint) => self.pay_in_token(payment_mint, fee, &royalties, remaining_accounts, signer_seeds)?,
+        }
+
+        let marketplace_key = self.marketplace.key();
+        let asset_key = self.asset.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            asset_key.as_ref(),
+            &[bumps.listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let core_program = &self.core_program.to_account_info();
+        let asset = &self.asset.to_account_info();
+        let collection = &self.collection.to_account_info();
+        let listing = &self.listing.to_account_info();
+        let payer = &self.lister.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+
+        // Thaw the asset before transfering it
+        let thaw_cpi = UpdatePluginV1Cpi::new(
+            core_program,
+            UpdatePluginV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(listing),
+                system_program,
+                log_wrapper: None,
+            },
+            UpdatePluginV1InstructionArgs {
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: false }),
+            },
+        );
+
+        thaw_cpi.invoke_signed(signer_seeds)?;
+
+        // Transfer the asset to the bidder > Core hands the delegates back to the new owner on transfer
+        let new_owner = &self.bidder.to_account_info();
+
+        let transfer_cpi = TransferV1Cpi::new(
+            core_program,
+            TransferV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(listing),
+                new_owner,
+                system_program: Some(system_program),
+                log_wrapper: None,
+            },
+            TransferV1InstructionArgs {
+                compression_proof: None,
+            },
+        );
+
+        transfer_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+
+    fn pay_in_sol(
+        &self,
+        fee: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        // Empty the bid vault > What is left after the Fee and Royalties goes to the lister
+        let seller_amount = amount_after_fees(self.bid_vault.lamports(), fee, royalties)?;
+
+        // Pay the lister, the Fee and the creators' Royalties out of the bid vault
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.bid_vault.to_account_info(),
+                seller: self.lister.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            signer_seeds,
+        )
+    }
+
+    fn pay_in_token(
+        &self,
+        payment_mint: Pubkey,
+        fee: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        let (Some(mint), Some(bid_vault_payment_ata), Some(lister_payment_ata), Some(fee_vault_payment_ata), Some(token_program)) = (
+            self.payment_mint.as_ref(),
+            self.bid_vault_payment_ata.as_ref(),
+            self.lister_payment_ata.as_ref(),
+            self.fee_vault_payment_ata.as_ref(),
+            self.payment_token_program.as_ref(),
+        ) else {
+            return err!(MarketplaceError::MissingPaymentAccounts);
+        };
+        require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+        // Empty the bid vault > What is left after the Fee and Royalties goes to the lister
+        let seller_amount = amount_after_fees(bid_vault_payment_ata.amount, fee, royalties)?;
+
+        // Pay the lister, the Fee and the creators' Royalties out of the bid vault
+        pay_sale_in_token(
+            &TokenPayment {
+                mint,
+                from: bid_vault_payment_ata.to_account_info(),
+                authority: self.bid_vault.to_account_info(),
+                seller: lister_payment_ata.to_account_info(),
+                fee_vault: fee_vault_payment_ata.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            signer_seeds,
+        )?;
+
+        // Close the bid vault ATA and give the rent back to the bidder
+        close_bid_vault_ata(
+            &bid_vault_payment_ata.to_account_info(),
+            &self.bid_vault.to_account_info(),
+            &self.bidder.to_account_info(),
+            &token_program.to_account_info(),
+            signer_seeds,
+        )
+    }
+}
@@ -0,0 +1,291 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+        types::TokenStandard,
+    }},
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::TransferArgs;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{creator_royalties, close_bid_vault_ata, amount_after_fees, pay_sale_in_sol, pay_sale_in_token, SolPayment, TokenPayment, check_nft_mint};
+
+// Accepts an offer on an NFT that was never listed > The owner signs, so the NFT is
+// transferred straight from their wallet without going through a listing.
+#[derive(Accounts)]
+pub struct AcceptOffer<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(mut)]
+    /// CHECK: no need to check it out
+    pub bidder: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = nft,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program,
+    )]
+    pub seller_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [b"offer", marketplace.key().as_ref(), nft.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        has_one = bidder,
+        has_one = nft,
+    )]
+    pub offer: Account<'info, OfferState>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", offer.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = payment_mint,
+        associated_token::authority = seller,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub seller_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = payment_mint,
+        associated_token::authority = fee_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub fee_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The payment mint may live under a different token program than the NFT
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptOffer<'info> {
+    pub fn accept_offer(
+        &mut self,
+        bumps: AcceptOfferBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        match self.metadata.token_standard {
+            Some(TokenStandard::NonFungible) | Some(TokenStandard::ProgrammableNonFungible) => {},
+            _ => return err!(MarketplaceError::InvalidTokenStandard),
+        }
+        check_nft_mint(&self.nft.to_account_info())?;
+
+        let offer_key = self.offer.key();
+        let seed = &[
+            b"bid_vault",
+            offer_key.as_ref(),
+            &[bumps.bid_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // Same split as a sale: Fee and Royalties come out of the offer price
+        let price = self.offer.price;
+        let fee = self.marketplace.fee_amount(price);
+        let royalties = creator_royalties(&self.metadata, price);
+        let seller_amount = amount_after_fees(price, fee, &royalties)?;
+
+        match self.offer.payment_mint {
+            None => self.pay_in_sol(fee, seller_amount, &royalties, remaining_accounts, signer_seeds)?,
+            Some(payment_mint) => self.pay_in_token(payment_mint, fee, seller_amount, &royalties, remaining_accounts, signer_seeds)?,
+        }
+
+        // Transfer the NFT > The seller is the owner, so no delegate is involved
+        let transfer_program = self.token_metadata_program.to_account_info();
+        let token = &self.seller_ata.to_account_info();
+        let token_owner = &self.seller.to_account_info();
+        let destination_token = &self.bidder_ata.to_account_info();
+        let destination_owner = &self.bidder.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let authority = &self.seller.to_account_info();
+        let payer = &self.seller.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            &transfer_program,
+            TransferCpiAccounts {
+                token,
+                token_owner,
+                destination_token,
+                destination_owner,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke()?;
+
+        Ok(())
+    }
+
+    fn pay_in_sol(
+        &self,
+        fee: u64,
+        seller_amount: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        // Pay the seller, the Fee and the creators' Royalties out of the bid vault
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.bid_vault.to_account_info(),
+                seller: self.seller.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            signer_seeds,
+        )
+    }
+
+    fn pay_in_token(
+        &self,
+        payment_mint: Pubkey,
+        fee: u64,
+        seller_amount: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        let (Some(mint), Some(bid_vault_payment_ata), Some(seller_payment_ata), Some(fee_vault_payment_ata), Some(token_program)) = (
+            self.payment_mint.as_ref(),
+            self.bid_vault_payment_ata.as_ref(),
+            self.seller_payment_ata.as_ref(),
+            self.fee_vault_payment_ata.as_ref(),
+            self.payment_token_program.as_ref(),
+        ) else {
+            return err!(MarketplaceError::MissingPaymentAccounts);
+        };
+        require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+        // Pay the seller, the Fee and the creators' Royalties out of the bid vault
+        pay_sale_in_token(
+            &TokenPayment {
+                mint,
+                from: bid_vault_payment_ata.to_account_info(),
+                authority: self.bid_vault.to_account_info(),
+                seller: seller_payment_ata.to_account_info(),
+                fee_vault: fee_vault_payment_ata.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            signer_seeds,
+        )?;
+
+        // Close the bid vault ATA and give the rent back to the bidder
+        close_bid_vault_ata(
+            &bid_vault_payment_ata.to_account_info(),
+            &self.bid_vault.to_account_info(),
+            &self.bidder.to_account_info(),
+            &token_program.to_account_info(),
+            signer_seeds,
+        )
+    }
+}
@@ -0,0 +1,238 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+        types::{TokenStandard, Collection},
+    }},
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::TransferArgs;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{creator_royalties, amount_after_fees, pay_sale_in_sol, SolPayment, release_swap_nfts, TransferPrograms, check_nft_mint};
+use crate::context::create_swap::SWAP_NFT_ACCOUNTS;
+
+// The taker hands over the NFT the maker asked for and gets the escrowed NFTs and lamports in return.
+// The lamports are what the maker pays for the taker's NFT, so the Fee and that NFT's Royalties come out of them,
+// while the NFT for NFT part of the trade has no price and pays nothing.
+#[derive(Accounts)]
+pub struct AcceptSwap<'info> {
+    #[account(mut)]
+    pub taker: Signer<'info>,
+    #[account(mut)]
+    pub maker: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = taker,
+        associated_token::token_program = token_program,
+    )]
+    pub taker_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = taker,
+        associated_token::mint = nft,
+        associated_token::authority = maker,
+        associated_token::token_program = token_program,
+    )]
+    pub maker_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = maker,
+        seeds = [b"swap", marketplace.key().as_ref(), maker.key().as_ref(), swap.seed.to_le_bytes().as_ref()],
+        bump,
+        has_one = maker,
+    )]
+    pub swap: Account<'info, SwapState>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", swap.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    // The NFT the taker gives
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> AcceptSwap<'info> {
+    pub fn accept_swap(
+        &mut self,
+        bumps: AcceptSwapBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        match self.metadata.token_standard {
+            Some(TokenStandard::NonFungible) | Some(TokenStandard::ProgrammableNonFungible) => {},
+            _ => return err!(MarketplaceError::InvalidTokenStandard),
+        }
+        check_nft_mint(&self.nft.to_account_info())?;
+        match self.swap.wanted {
+            SwapTarget::Nft(nft) => require_keys_eq!(self.nft.key(), nft, MarketplaceError::InvalidSwapNft),
+            SwapTarget::Collection(collection) => require!(self.metadata.collection == Some(Collection{verified: true, key: collection}), MarketplaceError::InvalidCollection),
+        }
+
+        // Transfer the wanted NFT to the maker > The taker is the owner, so no delegate is involved
+        let transfer_program = self.token_metadata_program.to_account_info();
+        let token = &self.taker_ata.to_account_info();
+        let token_owner = &self.taker.to_account_info();
+        let destination_token = &self.maker_ata.to_account_info();
+        let destination_owner = &self.maker.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let authority = &self.taker.to_account_info();
+        let payer = &self.taker.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            &transfer_program,
+            TransferCpiAccounts {
+                token,
+                token_owner,
+                destination_token,
+                destination_owner,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke()?;
+
+        // Release the escrowed NFTs to the taker, passed in order as remaining accounts ahead of the creators
+        let (nft_accounts, creator_accounts) = remaining_accounts.split_at(
+            remaining_accounts.len().min(self.swap.offered_nfts.len() * SWAP_NFT_ACCOUNTS)
+        );
+        let marketplace_key = self.marketplace.key();
+        let maker_key = self.maker.key();
+        let swap_seed = self.swap.seed.to_le_bytes();
+        let seed = &[
+            b"swap",
+            marketplace_key.as_ref(),
+            maker_key.as_ref(),
+            swap_seed.as_ref(),
+            &[bumps.swap]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        release_swap_nfts(
+            &self.swap,
+            &self.maker.to_account_info(),
+            &self.taker.to_account_info(),
+            &self.taker.to_account_info(),
+            nft_accounts,
+            &TransferPrograms {
+                token_metadata_program: self.token_metadata_program.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                sysvar_instructions: self.sysvar_instruction.to_account_info(),
+                spl_token_program: self.token_program.to_account_info(),
+                spl_ata_program: self.associated_token_program.to_account_info(),
+            },
+            signer_seeds,
+        )?;
+
+        if self.swap.amount == 0 {
+            return Ok(());
+        }
+
+        let fee = self.marketplace.fee_amount(self.swap.amount);
+        let royalties = creator_royalties(&self.metadata, self.swap.amount);
+
+        let swap_key = self.swap.key();
+        let seed = &[
+            b"bid_vault",
+            swap_key.as_ref(),
+            &[bumps.bid_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // The taker gets whatever else the bid vault holds, Royalties go to the creators of the taker's NFT
+        let taker_amount = amount_after_fees(self.bid_vault.lamports(), fee, &royalties)?;
+
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.bid_vault.to_account_info(),
+                seller: self.taker.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            taker_amount,
+            fee,
+            &royalties,
+            creator_accounts,
+            signer_seeds,
+        )
+    }
+}
@@ -3,7 +3,10 @@ pub use anchor_lang::{
     system_program::{Transfer, transfer}
 };
 
-pub use anchor_spl::token::Token;
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TransferChecked, transfer_checked, TokenInterface},
+    associated_token::AssociatedToken,
+};
 
 pub use crate::state::*;
 pub use crate::errors::*;
@@ -14,12 +17,12 @@ pub struct Bid<'info> {
     pub bidder: Signer<'info>,
 
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes(), marketplace.admin.key().as_ref()],
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
         bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
     #[account(
-        seeds = [b"listing", marketplace.key().as_ref()],
+        seeds = [b"listing", marketplace.key().as_ref(), listing.nft.as_ref()],
         bump,
     )]
     pub listing: Account<'info, Listing>,
@@ -33,11 +36,32 @@ pub struct Bid<'info> {
     pub bid: Account<'info, BidState>,
 
     #[account(
-        seeds = [b"listing_vault", bid.key().as_ref()],
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
         bump,
     )]
     pub bid_vault: SystemAccount<'info>,
 
+    // Only needed when the listing is settled in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -47,21 +71,49 @@ impl<'info> Bid<'info> {
         amount: u64,
     ) -> Result<()> {
 
+        require!(amount > 0, MarketplaceError::InvalidAmount);
+
         self.bid.set_inner(
             BidState {
                 bidder: self.bidder.key(),
                 price: amount,
+                payment_mint: self.listing.payment_mint,
             }
         );
 
-        let transfer_program = self.system_program.to_account_info();
-        let transfer_account = Transfer {
-            from: self.bidder.to_account_info(),
-            to: self.bid_vault.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(transfer_program, transfer_account);
+        match self.listing.payment_mint {
+            None => {
+                let transfer_program = self.system_program.to_account_info();
+                let transfer_account = Transfer {
+                    from: self.bidder.to_account_info(),
+                    to: self.bid_vault.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(transfer_program, transfer_account);
+
+                transfer(cpi_ctx, amount)?;
+            }
+            Some(payment_mint) => {
+                let (Some(mint), Some(bidder_payment_ata), Some(bid_vault_payment_ata)) = (
+                    self.payment_mint.as_ref(),
+                    self.bidder_payment_ata.as_ref(),
+                    self.bid_vault_payment_ata.as_ref(),
+                ) else {
+                    return err!(MarketplaceError::MissingPaymentAccounts);
+                };
+                require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+                let transfer_program = self.token_program.to_account_info();
+                let transfer_accounts = TransferChecked {
+                    from: bidder_payment_ata.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: bid_vault_payment_ata.to_account_info(),
+                    authority: self.bidder.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(transfer_program, transfer_accounts);
 
-        transfer(cpi_ctx, amount)?;
+                transfer_checked(cpi_ctx, amount, mint.decimals)?;
+            }
+        }
 
         Ok(())
     }
@@ -1,22 +1,19 @@
-pub use anchor_lang::{
-    prelude::*,
-    system_program::{Transfer, transfer}
-};
+pub use anchor_lang::prelude::*;
 
 pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
 
 use anchor_spl::{
-    token::{Mint, TokenAccount, Token}, 
+    token_interface::{Mint, TokenAccount, TokenInterface}, 
     metadata::{Metadata, MetadataAccount, MasterEditionAccount,
     mpl_token_metadata::instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs, UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs}}, 
     associated_token::AssociatedToken
 };
 
-use mpl_token_metadata::types::{TransferArgs, UnlockArgs, Creator};
-use solana_program::sysvar::instructions::{load_current_index_checked, load_instruction_at_checked};
+use mpl_token_metadata::types::{TransferArgs, UnlockArgs, TokenStandard};
 
 pub use crate::state::*;
 pub use crate::errors::*;
+use crate::utils::{creator_royalties, amount_after_fees, pay_sale_in_sol, pay_sale_in_token, SolPayment, TokenPayment, release_listing_vault};
 
 #[derive(Accounts)]
 pub struct Buy<'info> {
@@ -29,18 +26,20 @@ pub struct Buy<'info> {
         payer = buyer,
         associated_token::mint = nft,
         associated_token::authority = buyer,
+        associated_token::token_program = token_program,
     )]
-    pub buyer_ata: Account<'info, TokenAccount>,
+    pub buyer_ata: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
         associated_token::mint = nft,
         associated_token::authority = lister,
+        associated_token::token_program = token_program,
     )]
-    pub lister_ata: Account<'info, TokenAccount>,
+    pub lister_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes(), marketplace.admin.key().as_ref()],
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
         bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
@@ -53,134 +52,189 @@ pub struct Buy<'info> {
     #[account(
         mut,
         close = lister,
-        seeds = [b"listing", marketplace.key().as_ref()],
+        seeds = [b"listing", marketplace.key().as_ref(), nft.key().as_ref()],
         bump,
         has_one = lister,
         has_one = nft,
     )]
     pub listing: Account<'info, Listing>,
+    // Only needed for listings escrowed in the listing vault
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = listing,
+        associated_token::token_program = token_program,
+    )]
+    pub listing_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
     #[account(mut)]
-    pub nft: Account<'info, Mint>,
-    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
     pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
     pub edition: Account<'info, MasterEditionAccount>,
 
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    // Only needed when the listing is settled in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub buyer_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = payment_mint,
+        associated_token::authority = lister,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub lister_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = payment_mint,
+        associated_token::authority = fee_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub fee_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
     #[account(address = INSTRUCTIONS_ID)]
     /// CHECK: no need to check it out
     pub sysvar_instruction: AccountInfo<'info>,
     pub token_metadata_program: Program<'info, Metadata>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The payment mint may live under a different token program than the NFT
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> Buy<'info> {
     pub fn buy(
         &mut self,
+        max_price: u64,
+        expected_lister: Option<Pubkey>,
         bumps: BuyBumps,
+        remaining_accounts: &[AccountInfo<'info>],
     ) -> Result<()> {
 
-        // Pay for the NFT
-        let transfer_program = self.system_program.to_account_info();
-        let transfer_accounts = Transfer {
-            from: self.buyer.to_account_info(),
-            to: self.lister.to_account_info(),
-        };
-        let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
-
-        transfer(transfer_cpi, self.listing.price)?;
+        let now = Clock::get()?.unix_timestamp;
+        require!(!self.listing.is_expired(now), MarketplaceError::ListingExpired);
+        require!(self.listing.can_buy(&self.buyer.key()), MarketplaceError::ReservedListing);
 
-        // Pay the Fee
-        let transfer_program = self.system_program.to_account_info();
-        let transfer_accounts = Transfer {
-            from: self.buyer.to_account_info(),
-            to: self.fee_vault.to_account_info(),
-        };
-        let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
-        
-        transfer(transfer_cpi, (self.listing.price.checked_mul(self.marketplace.fee as u64).unwrap()).checked_div(10000).unwrap())?;
-
-        // Make sure that we pay Royalties
-        if self.metadata.seller_fee_basis_points != 0 && self.metadata.creators.is_some() {
-            let seller_fee_basis_points = self.metadata.seller_fee_basis_points;
-            let amount_to_split = self.listing.price
-                .checked_mul(seller_fee_basis_points as u64)
-                .unwrap()
-                .checked_div(10000)
-                .unwrap();
-            let creators = self.metadata.creators.as_ref().unwrap()
-                .iter()
-                .filter(|creator| creator.share > 0)
-                .collect::<Vec<&Creator>>();
-        
-            let index = load_current_index_checked(&self.sysvar_instruction.to_account_info())?;
-
-            for (i, _creator) in creators.iter().enumerate() {
-                let ix = load_instruction_at_checked(index as usize + 1 + i, &self.sysvar_instruction.to_account_info())?;
-                
-                let creator_amount = amount_to_split
-                    .checked_mul(_creator.share as u64)
-                    .unwrap()
-                    .checked_div(100)
-                    .unwrap();
-                
-                require_keys_eq!(ix.program_id, self.system_program.key(), InstrospectionError::InvalidTokenProgram);
-                require_eq!(ix.data[0], 2u8, InstrospectionError::InvalidIx);
-                require!(ix.data[4..12].eq(&creator_amount.to_le_bytes()), InstrospectionError::InvalidAmount);
-                require_keys_eq!(ix.accounts.get(1).unwrap().pubkey, _creator.address, InstrospectionError::InvalidCreator);
-            }
+        if let Some(expected_lister) = expected_lister {
+            require_keys_eq!(self.listing.lister, expected_lister, MarketplaceError::UnexpectedLister);
         }
-    
-        // Unlock the NFT before transfering it
-        let unlock_program = &self.token_program.to_account_info();
-        let authority = &self.listing.to_account_info();
-        let token_owner = &self.lister.to_account_info();
-        let token = &self.lister_ata.to_account_info();
-        let mint = &self.nft.to_account_info();
-        let metadata = &self.metadata.to_account_info();
-        let edition = &self.edition.to_account_info();
-        let payer = &self.buyer.to_account_info();
-        let system_program = &self.system_program.to_account_info();
-        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
-        let spl_token_program = &self.token_program.to_account_info();
 
-        let unlock_cpi = UnlockCpi::new(
-            unlock_program,
-            UnlockCpiAccounts {
-                authority,
-                token_owner: Some(token_owner),
-                token,
-                mint,
-                metadata,
-                edition: Some(edition),
-                token_record: None,
-                payer,
-                system_program,
-                sysvar_instructions,
-                spl_token_program: Some(spl_token_program),
-                authorization_rules_program: None,
-                authorization_rules: None,
-            },
-            UnlockInstructionArgs {
-                unlock_args: UnlockArgs::V1 {
-                    authorization_data: None,
-                },
-            }
-        );
+        // Nothing is charged on top of the price, so that is what the buyer's cap applies to
+        let price = self.listing.current_price(now);
+        require_gte!(max_price, price, MarketplaceError::PriceAboveMax);
+
+        // The Fee and Royalties come out of the listing price, same as when accepting a bid.
+        // Breaking: before this the buyer paid them on top, and the lister received the full price.
+        let fee = self.marketplace.fee_amount(price);
+        let royalties = creator_royalties(&self.metadata, price);
+        let seller_amount = amount_after_fees(price, fee, &royalties)?;
 
+        match self.listing.payment_mint {
+            None => self.pay_in_sol(fee, seller_amount, &royalties, remaining_accounts)?,
+            Some(payment_mint) => self.pay_in_token(payment_mint, fee, seller_amount, &royalties, remaining_accounts)?,
+        }
+    
         let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
         let seed = &[
             b"listing",
             marketplace_key.as_ref(),
+            nft_key.as_ref(),
             &[bumps.listing]
         ];
         let signer_seeds = &[&seed[..]];
 
-        unlock_cpi.invoke_signed(signer_seeds)?;
+        // An escrowed NFT is sent straight out of the listing vault
+        if self.listing.escrowed {
+            let Some(listing_vault) = self.listing_vault.as_ref() else {
+                return err!(MarketplaceError::MissingListingVault);
+            };
+            return release_listing_vault(
+                listing_vault,
+                &self.nft,
+                &self.buyer_ata.to_account_info(),
+                &self.listing.to_account_info(),
+                &self.lister.to_account_info(),
+                &self.token_program.to_account_info(),
+                signer_seeds,
+            );
+        }
+
+        // Unlock the NFT before transfering it > A pNFT listed with a Sale delegate was never locked
+        let is_programmable = self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible);
+
+        if !is_programmable {
+            let unlock_program = &self.token_metadata_program.to_account_info();
+            let authority = &self.listing.to_account_info();
+            let token_owner = &self.lister.to_account_info();
+            let token = &self.lister_ata.to_account_info();
+            let mint = &self.nft.to_account_info();
+            let metadata = &self.metadata.to_account_info();
+            let edition = &self.edition.to_account_info();
+            let payer = &self.buyer.to_account_info();
+            let system_program = &self.system_program.to_account_info();
+            let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+            let spl_token_program = &self.token_program.to_account_info();
+
+            let unlock_cpi = UnlockCpi::new(
+                unlock_program,
+                UnlockCpiAccounts {
+                    authority,
+                    token_owner: Some(token_owner),
+                    token,
+                    mint,
+                    metadata,
+                    edition: Some(edition),
+                    token_record: None,
+                    payer,
+                    system_program,
+                    sysvar_instructions,
+                    spl_token_program: Some(spl_token_program),
+                    authorization_rules_program: None,
+                    authorization_rules: None,
+                },
+                UnlockInstructionArgs {
+                    unlock_args: UnlockArgs::V1 {
+                        authorization_data: None,
+                    },
+                }
+            );
+
+            unlock_cpi.invoke_signed(signer_seeds)?;
+        }
         
         // Transfer the NFT > Then we close the account of the delegation so we don't need to revoke that.
-        let transfer_program = self.token_program.to_account_info();
+        let transfer_program = self.token_metadata_program.to_account_info();
         let token = &self.lister_ata.to_account_info();
         let token_owner = &self.lister.to_account_info();
         let destination_token = &self.buyer_ata.to_account_info();
@@ -205,16 +259,16 @@ impl<'info> Buy<'info> {
                 mint,
                 metadata,
                 edition: Some(edition),
-                token_record: None,
-                destination_token_record: None,
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
                 authority,
                 payer,
                 system_program,
                 sysvar_instructions,
                 spl_token_program,
                 spl_ata_program,
-                authorization_rules_program: None,
-                authorization_rules: None,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
             },
             TransferInstructionArgs {
                 transfer_args: TransferArgs::V1 {
@@ -228,4 +282,66 @@ impl<'info> Buy<'info> {
     
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn pay_in_sol(
+        &self,
+        fee: u64,
+        seller_amount: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        // Pay the lister, the Fee and the creators' Royalties
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.buyer.to_account_info(),
+                seller: self.lister.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            &[],
+        )
+    }
+
+    fn pay_in_token(
+        &self,
+        payment_mint: Pubkey,
+        fee: u64,
+        seller_amount: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let (Some(mint), Some(buyer_payment_ata), Some(lister_payment_ata), Some(fee_vault_payment_ata), Some(token_program)) = (
+            self.payment_mint.as_ref(),
+            self.buyer_payment_ata.as_ref(),
+            self.lister_payment_ata.as_ref(),
+            self.fee_vault_payment_ata.as_ref(),
+            self.payment_token_program.as_ref(),
+        ) else {
+            return err!(MarketplaceError::MissingPaymentAccounts);
+        };
+        require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+        // Pay the lister, the Fee and the creators' Royalties
+        pay_sale_in_token(
+            &TokenPayment {
+                mint,
+                from: buyer_payment_ata.to_account_info(),
+                authority: self.buyer.to_account_info(),
+                seller: lister_payment_ata.to_account_info(),
+                fee_vault: fee_vault_payment_ata.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            &[],
+        )
+    }
+}
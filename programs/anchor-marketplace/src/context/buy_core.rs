@@ -0,0 +1,239 @@
+pub use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    associated_token::AssociatedToken
+};
+use mpl_core::{
+    instructions::{
+        UpdatePluginV1Cpi, UpdatePluginV1CpiAccounts, UpdatePluginV1InstructionArgs,
+        TransferV1Cpi, TransferV1CpiAccounts, TransferV1InstructionArgs,
+    },
+    types::{FreezeDelegate, Plugin},
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{core_royalties, amount_after_fees, pay_sale_in_sol, pay_sale_in_token, SolPayment, TokenPayment};
+
+#[derive(Accounts)]
+pub struct BuyCore<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut, address = listing.lister.key())]
+    pub lister: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), asset.key().as_ref()],
+        bump,
+        has_one = lister,
+        has_one = collection,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut, owner = mpl_core::ID)]
+    /// CHECK: checked by the listing seeds
+    pub asset: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the listing
+    pub collection: AccountInfo<'info>,
+
+    // Only needed when the listing is settled in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub buyer_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = payment_mint,
+        associated_token::authority = lister,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub lister_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = payment_mint,
+        associated_token::authority = fee_vault,
+        associated_token::token_program = payment_token_program,
+    )]
+    pub fee_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: checked by address
+    pub core_program: AccountInfo<'info>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub payment_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> BuyCore<'info> {
+    pub fn buy_core(
+        &mut self,
+        max_price: u64,
+        expected_lister: Option<Pubkey>,
+        bumps: BuyCoreBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(!self.listing.is_expired(now), MarketplaceError::ListingExpired);
+        require!(self.listing.can_buy(&self.buyer.key()), MarketplaceError::ReservedListing);
+
+        if let Some(expected_lister) = expected_lister {
+            require_keys_eq!(self.listing.lister, expected_lister, MarketplaceError::UnexpectedLister);
+        }
+
+        // Nothing is charged on top of the price, so that is what the buyer's cap applies to
+        let price = self.listing.current_price(now);
+        require_gte!(max_price, price, MarketplaceError::PriceAboveMax);
+
+        // The Fee and Royalties come out of the listing price, same as for Token Metadata NFTs
+        let fee = self.marketplace.fee_amount(price);
+        let royalties = core_royalties(&self.asset, &self.collection, price);
+        let seller_amount = amount_after_fees(price, fee, &royalties)?;
+
+        match self.listing.payment_mint {
+            None => self.pay_in_sol(fee, seller_amount, &royalties, remaining_accounts)?,
+            Some(payment_mint) => self.pay_in_token(payment_mint, fee, seller_amount, &royalties, remaining_accounts)?,
+        }
+
+        let marketplace_key = self.marketplace.key();
+        let asset_key = self.asset.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            asset_key.as_ref(),
+            &[bumps.listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let core_program = &self.core_program.to_account_info();
+        let asset = &self.asset.to_account_info();
+        let collection = &self.collection.to_account_info();
+        let listing = &self.listing.to_account_info();
+        let payer = &self.buyer.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+
+        // Thaw the asset before transfering it
+        let thaw_cpi = UpdatePluginV1Cpi::new(
+            core_program,
+            UpdatePluginV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(listing),
+                system_program,
+                log_wrapper: None,
+            },
+            UpdatePluginV1InstructionArgs {
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: false }),
+            },
+        );
+
+        thaw_cpi.invoke_signed(signer_seeds)?;
+
+        // Transfer the asset > Core hands the delegates back to the new owner on transfer
+        let new_owner = &self.buyer.to_account_info();
+
+        let transfer_cpi = TransferV1Cpi::new(
+            core_program,
+            TransferV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(listing),
+                new_owner,
+                system_program: Some(system_program),
+                log_wrapper: None,
+            },
+            TransferV1InstructionArgs {
+                compression_proof: None,
+            },
+        );
+
+        transfer_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+
+    fn pay_in_sol(
+        &self,
+        fee: u64,
+        seller_amount: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        // Pay the lister, the Fee and the creators' Royalties
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.buyer.to_account_info(),
+                seller: self.lister.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            &[],
+        )
+    }
+
+    fn pay_in_token(
+        &self,
+        payment_mint: Pubkey,
+        fee: u64,
+        seller_amount: u64,
+        royalties: &[(Pubkey, u64)],
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let (Some(mint), Some(buyer_payment_ata), Some(lister_payment_ata), Some(fee_vault_payment_ata), Some(token_program)) = (
+            self.payment_mint.as_ref(),
+            self.buyer_payment_ata.as_ref(),
+            self.lister_payment_ata.as_ref(),
+            self.fee_vault_payment_ata.as_ref(),
+            self.payment_token_program.as_ref(),
+        ) else {
+            return err!(MarketplaceError::MissingPaymentAccounts);
+        };
+        require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+        // Pay the lister, the Fee and the creators' Royalties
+        pay_sale_in_token(
+            &TokenPayment {
+                mint,
+                from: buyer_payment_ata.to_account_info(),
+                authority: self.buyer.to_account_info(),
+                seller: lister_payment_ata.to_account_info(),
+                fee_vault: fee_vault_payment_ata.to_account_info(),
+                token_program: token_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            royalties,
+            remaining_accounts,
+            &[],
+        )
+    }
+}
@@ -0,0 +1,215 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, CloseAccount, close_account, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+        types::TokenStandard,
+    }},
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::TransferArgs;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{creator_royalties, amount_after_fees, pay_sale_in_sol, SolPayment};
+
+#[derive(Accounts)]
+pub struct BuyFromPool<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(mut)]
+    pub owner: SystemAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = nft,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program,
+    )]
+    pub buyer_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"pool", marketplace.key().as_ref(), owner.key().as_ref(), pool.seed.to_le_bytes().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"pool_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> BuyFromPool<'info> {
+    pub fn buy_from_pool(
+        &mut self,
+        max_price: u64,
+        bumps: BuyFromPoolBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        require!(self.pool.kind != PoolKind::Token, MarketplaceError::InvalidPoolSide);
+
+        // The price moves up one step with every NFT the pool sells
+        let price = self.pool.sell_price().ok_or(MarketplaceError::InvalidAmount)?;
+        require_gte!(max_price, price, MarketplaceError::PriceAboveMax);
+        require!(price > 0, MarketplaceError::InvalidPoolPrice);
+
+        self.pool.spot_price = self.pool.curve.step_up(self.pool.spot_price).ok_or(MarketplaceError::InvalidAmount)?;
+        self.pool.nft_count = self.pool.nft_count.checked_sub(1).ok_or(MarketplaceError::InvalidAmount)?;
+
+        // Same split as a sale: Fee and Royalties come out of the price, the rest goes to the pool vault
+        let fee = self.marketplace.fee_amount(price);
+        let royalties = creator_royalties(&self.metadata, price);
+        let pool_amount = amount_after_fees(price, fee, &royalties)?;
+
+        // Royalties go to the creators, passed in order as remaining accounts
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.buyer.to_account_info(),
+                seller: self.pool_vault.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            pool_amount,
+            fee,
+            &royalties,
+            remaining_accounts,
+            &[],
+        )?;
+
+        let marketplace_key = self.marketplace.key();
+        let owner_key = self.owner.key();
+        let pool_seed = self.pool.seed.to_le_bytes();
+        let seed = &[
+            b"pool",
+            marketplace_key.as_ref(),
+            owner_key.as_ref(),
+            pool_seed.as_ref(),
+            &[bumps.pool]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // Transfer the NFT > The pool signs as the owner of its token account
+        let transfer_program = self.token_metadata_program.to_account_info();
+        let token = &self.pool_ata.to_account_info();
+        let token_owner = &self.pool.to_account_info();
+        let destination_token = &self.buyer_ata.to_account_info();
+        let destination_owner = &self.buyer.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let payer = &self.buyer.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            &transfer_program,
+            TransferCpiAccounts {
+                token,
+                token_owner,
+                destination_token,
+                destination_owner,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
+                authority: token_owner,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke_signed(signer_seeds)?;
+
+        // A pNFT's token account stays frozen, so only a regular NFT's empty account can be closed
+        if self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible) {
+            return Ok(());
+        }
+
+        let close_program = self.token_program.to_account_info();
+        let close_accounts = CloseAccount {
+            account: self.pool_ata.to_account_info(),
+            destination: self.owner.to_account_info(),
+            authority: self.pool.to_account_info(),
+        };
+        let close_cpi = CpiContext::new_with_signer(close_program, close_accounts, signer_seeds);
+
+        close_account(close_cpi)
+    }
+}
@@ -1,29 +1,20 @@
-pub use anchor_lang::{
-    prelude::*,
-    system_program::{Transfer, transfer}
+pub use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    associated_token::AssociatedToken,
 };
-pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
-use anchor_spl::token::{ Token, CloseAccount, close_account};
 
 pub use crate::state::*;
-pub use crate::errors::*;
+use crate::utils::{refund_bid_vault, BidRefund};
 
 #[derive(Accounts)]
 pub struct CancelBid<'info> {
     #[account(mut)]
     pub bidder: Signer<'info>,
 
-    #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes(), marketplace.admin.key().as_ref()],
-        bump,
-    )]
-    pub marketplace: Account<'info, Marketplace>,
-    #[account(
-        mut, 
-        seeds = [b"listing", marketplace.key().as_ref()],
-        bump,
-    )]
-    pub listing: Account<'info, Listing>,
+    /// CHECK: only used to derive the bid, the listing may already be bought or delisted
+    pub listing: AccountInfo<'info>,
     #[account(
         mut,
         close = bidder,
@@ -33,12 +24,32 @@ pub struct CancelBid<'info> {
     )]
     pub bid: Account<'info, BidState>,
     #[account(
-        seeds = [b"listing_vault", bid.key().as_ref()],
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
         bump,
     )]
     pub bid_vault: SystemAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -50,23 +61,25 @@ impl<'info> CancelBid<'info> {
 
         let bid_key = self.bid.key();
         let seed = &[
-            b"listing_vault",
+            b"bid_vault",
             bid_key.as_ref(),
-            &[bumps.bid]
+            &[bumps.bid_vault]
         ];
         let signer_seeds = &[&seed[..]];
 
-        // Close the listing vault (ATA) == return the bid amount to the bidder
-        let close_program = self.token_program.to_account_info();
-        let close_accounts = CloseAccount {
-            account: self.bid_vault.to_account_info(),
-            destination: self.bidder.to_account_info(),
-            authority: self.bid.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new_with_signer(close_program, close_accounts, signer_seeds);
-
-        close_account(cpi_ctx)?;
-        
-        Ok(())
+        // Empty the bid vault == return the bid amount to the bidder
+        refund_bid_vault(
+            &BidRefund {
+                bid_vault: self.bid_vault.to_account_info(),
+                bidder: self.bidder.to_account_info(),
+                payment_mint: self.payment_mint.as_ref(),
+                bidder_payment_ata: self.bidder_payment_ata.as_ref(),
+                bid_vault_payment_ata: self.bid_vault_payment_ata.as_ref(),
+                token_program: self.token_program.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            self.bid.payment_mint,
+            signer_seeds,
+        )
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,109 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::TokenInterface,
+    metadata::Metadata,
+    associated_token::AssociatedToken,
+};
+
+pub use crate::state::*;
+use crate::utils::{release_swap_nfts, TransferPrograms};
+
+#[derive(Accounts)]
+pub struct CancelSwap<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        close = maker,
+        seeds = [b"swap", marketplace.key().as_ref(), maker.key().as_ref(), swap.seed.to_le_bytes().as_ref()],
+        bump,
+        has_one = maker,
+    )]
+    pub swap: Account<'info, SwapState>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", swap.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CancelSwap<'info> {
+    pub fn cancel_swap(
+        &mut self,
+        bumps: CancelSwapBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        // Give the escrowed NFTs back to the maker, passed in order as remaining accounts
+        let marketplace_key = self.marketplace.key();
+        let maker_key = self.maker.key();
+        let swap_seed = self.swap.seed.to_le_bytes();
+        let seed = &[
+            b"swap",
+            marketplace_key.as_ref(),
+            maker_key.as_ref(),
+            swap_seed.as_ref(),
+            &[bumps.swap]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        release_swap_nfts(
+            &self.swap,
+            &self.maker.to_account_info(),
+            &self.maker.to_account_info(),
+            &self.maker.to_account_info(),
+            remaining_accounts,
+            &TransferPrograms {
+                token_metadata_program: self.token_metadata_program.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                sysvar_instructions: self.sysvar_instruction.to_account_info(),
+                spl_token_program: self.token_program.to_account_info(),
+                spl_ata_program: self.associated_token_program.to_account_info(),
+            },
+            signer_seeds,
+        )?;
+
+        if self.swap.amount == 0 {
+            return Ok(());
+        }
+
+        // Empty the bid vault == return the lamports to the maker
+        let swap_key = self.swap.key();
+        let seed = &[
+            b"bid_vault",
+            swap_key.as_ref(),
+            &[bumps.bid_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let transfer_program = self.system_program.to_account_info();
+        let transfer_accounts = Transfer {
+            from: self.bid_vault.to_account_info(),
+            to: self.maker.to_account_info(),
+        };
+        let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+        transfer(transfer_cpi, self.bid_vault.lamports())
+    }
+}
@@ -0,0 +1,88 @@
+pub use anchor_lang::prelude::*;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    associated_token::AssociatedToken,
+};
+
+pub use crate::state::*;
+use crate::utils::{refund_bid_vault, BidRefund};
+
+#[derive(Accounts)]
+pub struct CancelTraitBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [b"trait_bid", marketplace.key().as_ref(), trait_bid.root.as_ref(), bidder.key().as_ref()],
+        bump,
+        has_one = bidder,
+    )]
+    pub trait_bid: Account<'info, TraitBidState>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", trait_bid.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CancelTraitBid<'info> {
+    pub fn cancel_trait_bid(
+        &mut self,
+        bumps: CancelTraitBidBumps
+    ) -> Result<()> {
+
+        let bid_key = self.trait_bid.key();
+        let seed = &[
+            b"bid_vault",
+            bid_key.as_ref(),
+            &[bumps.bid_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // Empty the bid vault == return the bid amount to the bidder
+        refund_bid_vault(
+            &BidRefund {
+                bid_vault: self.bid_vault.to_account_info(),
+                bidder: self.bidder.to_account_info(),
+                payment_mint: self.payment_mint.as_ref(),
+                bidder_payment_ata: self.bidder_payment_ata.as_ref(),
+                bid_vault_payment_ata: self.bid_vault_payment_ata.as_ref(),
+                token_program: self.token_program.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            self.trait_bid.payment_mint,
+            signer_seeds,
+        )
+    }
+}
@@ -0,0 +1,206 @@
+pub use anchor_lang::prelude::*;
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface}, 
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+        mpl_token_metadata::instructions::{UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs, RevokeCpi, RevokeCpiAccounts, RevokeInstructionArgs}, 
+    },
+    associated_token::AssociatedToken,
+};
+use mpl_token_metadata::types::{RevokeArgs, UnlockArgs, TokenStandard};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::release_listing_vault;
+
+// Anyone can clean up an expired listing, the rent goes back to the lister
+#[derive(Accounts)]
+pub struct CloseExpiredListing<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub lister: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = lister,
+        associated_token::token_program = token_program,
+    )]
+    pub lister_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        close = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), nft.key().as_ref()],
+        bump,
+        has_one = lister,
+        has_one = nft,
+        constraint = listing.is_expired(Clock::get()?.unix_timestamp) @ MarketplaceError::ListingNotExpired,
+    )]
+    pub listing: Account<'info, Listing>,
+    // Only needed for listings escrowed in the listing vault
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = listing,
+        associated_token::token_program = token_program,
+    )]
+    pub listing_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CloseExpiredListing<'info> {
+    pub fn close_expired_listing(
+        &mut self,
+        bumps: CloseExpiredListingBumps,
+    ) -> Result<()> {
+
+        let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[bumps.listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // An escrowed NFT is sent straight out of the listing vault
+        if self.listing.escrowed {
+            let Some(listing_vault) = self.listing_vault.as_ref() else {
+                return err!(MarketplaceError::MissingListingVault);
+            };
+            return release_listing_vault(
+                listing_vault,
+                &self.nft,
+                &self.lister_ata.to_account_info(),
+                &self.listing.to_account_info(),
+                &self.lister.to_account_info(),
+                &self.token_program.to_account_info(),
+                signer_seeds,
+            );
+        }
+
+        // A pNFT listed with a Sale delegate was never locked
+        let is_programmable = self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible);
+
+        if !is_programmable {
+            let unlock_program = &self.token_metadata_program.to_account_info();
+            let authority = &self.listing.to_account_info();
+            let token_owner = &self.lister.to_account_info();
+            let token = &self.lister_ata.to_account_info();
+            let mint = &self.nft.to_account_info();
+            let metadata = &self.metadata.to_account_info();
+            let edition = &self.edition.to_account_info();
+            let payer = &self.payer.to_account_info();
+            let system_program = &self.system_program.to_account_info();
+            let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+            let spl_token_program = &self.token_program.to_account_info();
+
+            let unlock_cpi = UnlockCpi::new(
+                unlock_program,
+                UnlockCpiAccounts {
+                    authority,
+                    token_owner: Some(token_owner),
+                    token,
+                    mint,
+                    metadata,
+                    edition: Some(edition),
+                    token_record: None,
+                    payer,
+                    system_program,
+                    sysvar_instructions,
+                    spl_token_program: Some(spl_token_program),
+                    authorization_rules_program: None,
+                    authorization_rules: None,
+                },
+                UnlockInstructionArgs {
+                    unlock_args: UnlockArgs::V1 {
+                        authorization_data: None,
+                    },
+                }
+            );
+
+            unlock_cpi.invoke_signed(signer_seeds)?;
+        }
+    
+        // The lister isn't signing, so the listing revokes itself as the delegate
+        let revoke_program = &self.token_metadata_program.to_account_info();
+        let delegate = &self.listing.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let token = &self.lister_ata.to_account_info();
+        let authority = &self.listing.to_account_info();
+        let payer = &self.payer.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        let revoke_cpi = RevokeCpi::new(
+            revoke_program,
+            RevokeCpiAccounts {
+                delegate_record: None,
+                delegate,
+                metadata,
+                master_edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                mint,
+                token: Some(token),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            RevokeInstructionArgs {
+                revoke_args: if is_programmable { RevokeArgs::SaleV1 } else { RevokeArgs::StandardV1 },
+            },
+        );
+
+        revoke_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+
+}
\ No newline at end of file
@@ -0,0 +1,115 @@
+pub use anchor_lang::prelude::*;
+
+use mpl_core::{
+    instructions::{
+        UpdatePluginV1Cpi, UpdatePluginV1CpiAccounts, UpdatePluginV1InstructionArgs,
+        RevokePluginAuthorityV1Cpi, RevokePluginAuthorityV1CpiAccounts, RevokePluginAuthorityV1InstructionArgs,
+    },
+    types::{FreezeDelegate, Plugin, PluginType},
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+// Same as `close_expired_listing` for a Core asset. The lister isn't signing, so the listing
+// thaws the asset and revokes its own delegate authorities instead of removing the plugins.
+#[derive(Accounts)]
+pub struct CloseExpiredListingCore<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut)]
+    pub lister: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        close = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), asset.key().as_ref()],
+        bump,
+        has_one = lister,
+        has_one = collection,
+        constraint = listing.is_expired(Clock::get()?.unix_timestamp) @ MarketplaceError::ListingNotExpired,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut, owner = mpl_core::ID)]
+    /// CHECK: checked by the listing seeds
+    pub asset: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the listing
+    pub collection: AccountInfo<'info>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: checked by address
+    pub core_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CloseExpiredListingCore<'info> {
+    pub fn close_expired_listing_core(
+        &mut self,
+        bumps: CloseExpiredListingCoreBumps,
+    ) -> Result<()> {
+
+        let marketplace_key = self.marketplace.key();
+        let asset_key = self.asset.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            asset_key.as_ref(),
+            &[bumps.listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let core_program = &self.core_program.to_account_info();
+        let asset = &self.asset.to_account_info();
+        let collection = &self.collection.to_account_info();
+        let payer = &self.payer.to_account_info();
+        let listing = &self.listing.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+
+        // Thaw the asset > Only the listing holds the Freeze delegate
+        let thaw_cpi = UpdatePluginV1Cpi::new(
+            core_program,
+            UpdatePluginV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(listing),
+                system_program,
+                log_wrapper: None,
+            },
+            UpdatePluginV1InstructionArgs {
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: false }),
+            },
+        );
+
+        thaw_cpi.invoke_signed(signer_seeds)?;
+
+        // Hand both delegates back to the owner > A delegate can revoke itself
+        for plugin_type in [PluginType::FreezeDelegate, PluginType::TransferDelegate] {
+            let revoke_cpi = RevokePluginAuthorityV1Cpi::new(
+                core_program,
+                RevokePluginAuthorityV1CpiAccounts {
+                    asset,
+                    collection: Some(collection),
+                    payer,
+                    authority: Some(listing),
+                    system_program,
+                    log_wrapper: None,
+                },
+                RevokePluginAuthorityV1InstructionArgs {
+                    plugin_type,
+                },
+            );
+
+            revoke_cpi.invoke_signed(signer_seeds)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TransferChecked, transfer_checked, TokenInterface},
+    associated_token::AssociatedToken,
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+pub struct CollectionBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    pub collection: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = bidder,
+        seeds = [b"collection_bid", marketplace.key().as_ref(), collection.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        space = CollectionBidState::INIT_SPACE,
+    )]
+    pub collection_bid: Account<'info, CollectionBidState>,
+
+    #[account(
+        mut,
+        seeds = [b"bid_vault", collection_bid.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CollectionBid<'info> {
+    pub fn collection_bid(
+        &mut self,
+        price: u64,
+        quantity: u64,
+        payment_mint: Option<Pubkey>,
+    ) -> Result<()> {
+
+        require!(price > 0 && quantity > 0, MarketplaceError::InvalidAmount);
+        if let Some(payment_mint) = payment_mint {
+            require!(self.marketplace.payment_mints.contains(&payment_mint), MarketplaceError::InvalidPaymentMint);
+        }
+
+        // The vault holds the price of every NFT the bidder is willing to take
+        let amount = price.checked_mul(quantity).ok_or(MarketplaceError::InvalidAmount)?;
+
+        self.collection_bid.set_inner(
+            CollectionBidState {
+                bidder: self.bidder.key(),
+                collection: self.collection.key(),
+                price,
+                quantity,
+                payment_mint,
+            }
+        );
+
+        match payment_mint {
+            None => {
+                let transfer_program = self.system_program.to_account_info();
+                let transfer_account = Transfer {
+                    from: self.bidder.to_account_info(),
+                    to: self.bid_vault.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(transfer_program, transfer_account);
+
+                transfer(cpi_ctx, amount)?;
+            }
+            Some(payment_mint) => {
+                let (Some(mint), Some(bidder_payment_ata), Some(bid_vault_payment_ata)) = (
+                    self.payment_mint.as_ref(),
+                    self.bidder_payment_ata.as_ref(),
+                    self.bid_vault_payment_ata.as_ref(),
+                ) else {
+                    return err!(MarketplaceError::MissingPaymentAccounts);
+                };
+                require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+                let transfer_program = self.token_program.to_account_info();
+                let transfer_accounts = TransferChecked {
+                    from: bidder_payment_ata.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: bid_vault_payment_ata.to_account_info(),
+                    authority: self.bidder.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(transfer_program, transfer_accounts);
+
+                transfer_checked(cpi_ctx, amount, mint.decimals)?;
+            }
+        }
+
+        Ok(())
+    }
+}
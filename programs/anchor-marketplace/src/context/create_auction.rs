@@ -0,0 +1,218 @@
+pub use anchor_lang::prelude::*;
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface}, 
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::{
+        instructions::{DelegateCpi, DelegateCpiAccounts, DelegateInstructionArgs, LockCpi, LockCpiAccounts, LockInstructionArgs},
+        types::{TokenStandard, Collection},
+    }}, 
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::{DelegateArgs, LockArgs };
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::check_nft_mint;
+
+// Starts an English auction > The NFT gets the same delegate and lock as a listing,
+// held by the auction PDA until `settle_auction`.
+#[derive(Accounts)]
+pub struct CreateAuction<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = nft,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program,
+    )]
+    pub seller_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        init,
+        payer = seller,
+        seeds = [b"auction", marketplace.key().as_ref(), nft.key().as_ref()],
+        bump,
+        space = Auction::INIT_SPACE,
+    )]
+    pub auction: Account<'info, Auction>,
+
+    pub collection: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateAuction<'info> {
+    pub fn create_auction(
+        &mut self,
+        reserve_price: u64,
+        min_increment: u64,
+        start_time: i64,
+        end_time: i64,
+        extension_window: i64,
+        bumps: CreateAuctionBumps,
+    ) -> Result<()> {
+
+        let is_programmable = match self.metadata.token_standard {
+            Some(TokenStandard::NonFungible) => false,
+            Some(TokenStandard::ProgrammableNonFungible) => true,
+            _ => return err!(MarketplaceError::InvalidTokenStandard),
+        };
+        require!(self.metadata.collection.clone().unwrap() == Collection{verified: true, key: self.collection.key()}, MarketplaceError::InvalidCollection); 
+        check_nft_mint(&self.nft.to_account_info())?;
+        require!(end_time > start_time && end_time > Clock::get()?.unix_timestamp, MarketplaceError::InvalidAuctionTimes);
+        require!((0..=Auction::MAX_EXTENSION_WINDOW).contains(&extension_window), MarketplaceError::InvalidAuctionTimes);
+
+        self.auction.set_inner(
+            Auction {
+                seller: self.seller.key(),
+                nft: self.nft.key(),
+                collection: self.collection.key(),
+                reserve_price,
+                min_increment,
+                start_time,
+                end_time,
+                extension_window,
+                highest_bidder: None,
+                highest_bid: 0,
+            }
+        );
+
+        let token_metadata_program = &self.token_metadata_program.to_account_info();
+        let delegate = &self.auction.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let master_edition = &self.edition.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let token = &self.seller_ata.to_account_info();
+        let authority = &self.seller.to_account_info();
+        let payer = &self.seller.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let token_record = self.token_record.as_ref();
+        let authorization_rules = self.authorization_rules.as_ref();
+        let authorization_rules_program = self.authorization_rules_program.as_ref();
+
+        // A pNFT is frozen by the Sale delegate itself, while a regular NFT gets a Standard delegate that we lock
+        let delegate_args = if is_programmable {
+            require!(token_record.is_some(), MarketplaceError::MissingTokenRecord);
+            DelegateArgs::SaleV1 {
+                amount: 1,
+                authorization_data: None,
+            }
+        } else {
+            DelegateArgs::StandardV1 {
+                amount: 1,
+            }
+        };
+
+        let delegate_cpi = DelegateCpi::new(
+            token_metadata_program,
+            DelegateCpiAccounts {
+                delegate_record: None,
+                delegate,
+                metadata,
+                master_edition: Some(master_edition),
+                token_record,
+                mint,
+                token: Some(token),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program,
+                authorization_rules,
+                
+            },
+            DelegateInstructionArgs {
+                delegate_args,
+            },
+        );
+
+        delegate_cpi.invoke()?;
+
+        if is_programmable {
+            return Ok(());
+        }
+
+        let authority = &self.auction.to_account_info();
+        let token_owner = &self.seller.to_account_info();
+        
+        let lock_cpi = LockCpi::new(
+            token_metadata_program,
+            LockCpiAccounts {
+                authority,
+                token_owner: Some(token_owner),
+                token, 
+                mint,
+                metadata,
+                edition: Some(master_edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            LockInstructionArgs {
+                lock_args: LockArgs::V1 {
+                    authorization_data: None,
+                },
+            },
+        );
+
+        let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
+        let seed = &[
+            b"auction",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[bumps.auction]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        lock_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
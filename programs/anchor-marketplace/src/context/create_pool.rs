@@ -0,0 +1,66 @@
+pub use anchor_lang::prelude::*;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct CreatePool<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"pool", marketplace.key().as_ref(), owner.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        space = Pool::INIT_SPACE,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreatePool<'info> {
+    // The pool starts empty > The owner funds it with deposit_pool_sol and deposit_pool_nft
+    pub fn create_pool(
+        &mut self,
+        seed: u64,
+        collection: Pubkey,
+        kind: PoolKind,
+        curve: BondingCurve,
+        spot_price: u64,
+        spread: u16,
+    ) -> Result<()> {
+
+        require!(spot_price > 0, MarketplaceError::InvalidAmount);
+        // A linear pool must be able to buy at least once, one step below the spot price
+        if let BondingCurve::Linear { delta } = curve {
+            require_gt!(spot_price, delta, MarketplaceError::InvalidPoolPrice);
+        }
+        require!(
+            spread <= Pool::MAX_SPREAD && (spread == 0 || kind == PoolKind::Trade),
+            MarketplaceError::InvalidPoolSpread
+        );
+
+        self.pool.set_inner(
+            Pool {
+                owner: self.owner.key(),
+                seed,
+                collection,
+                kind,
+                curve,
+                spot_price,
+                spread,
+                nft_count: 0,
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::TokenInterface,
+    metadata::{Metadata, MetadataAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+        types::TokenStandard,
+    }},
+    associated_token::{AssociatedToken, get_associated_token_address_with_program_id},
+};
+use mpl_token_metadata::types::TransferArgs;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{check_metadata_accounts, check_nft_mint};
+
+// Accounts passed for each escrowed NFT of a swap: token, destination_token, nft, metadata, edition.
+// The escrow side is always the swap's associated token account for the NFT.
+pub const SWAP_NFT_ACCOUNTS: usize = 5;
+
+#[derive(Accounts)]
+#[instruction(seed: u64)]
+pub struct CreateSwap<'info> {
+    #[account(mut)]
+    pub maker: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        init,
+        payer = maker,
+        seeds = [b"swap", marketplace.key().as_ref(), maker.key().as_ref(), seed.to_le_bytes().as_ref()],
+        bump,
+        space = SwapState::INIT_SPACE,
+    )]
+    pub swap: Account<'info, SwapState>,
+    #[account(
+        mut,
+        seeds = [b"bid_vault", swap.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateSwap<'info> {
+    // Escrows the NFTs passed as remaining accounts, and `amount` lamports on top if it isn't 0.
+    // Only regular NFTs can be escrowed, a pNFT would need its token records moved along.
+    pub fn create_swap(
+        &mut self,
+        seed: u64,
+        wanted: SwapTarget,
+        amount: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let nfts = remaining_accounts.chunks_exact(SWAP_NFT_ACCOUNTS);
+        require!(nfts.len() > 0 && nfts.remainder().is_empty(), MarketplaceError::MissingSwapAccounts);
+        require_gte!(SwapState::MAX_OFFERED_NFTS, nfts.len(), MarketplaceError::TooManySwapNfts);
+
+        let mut offered_nfts = Vec::with_capacity(nfts.len());
+
+        for accounts in nfts {
+            let [token, destination_token, nft, metadata, edition] = accounts else {
+                return err!(MarketplaceError::MissingSwapAccounts);
+            };
+
+            check_metadata_accounts(nft, metadata, edition)?;
+            check_nft_mint(nft)?;
+            let metadata_account = Account::<MetadataAccount>::try_from(metadata)?;
+            require!(metadata_account.token_standard == Some(TokenStandard::NonFungible), MarketplaceError::InvalidTokenStandard);
+            require_keys_eq!(
+                destination_token.key(),
+                get_associated_token_address_with_program_id(&self.swap.key(), &nft.key(), &self.token_program.key()),
+                MarketplaceError::MissingSwapAccounts
+            );
+
+            // Escrow the NFT > The swap owns it until the swap is accepted or cancelled
+            let transfer_program = self.token_metadata_program.to_account_info();
+            let token_owner = &self.maker.to_account_info();
+            let destination_owner = &self.swap.to_account_info();
+            let payer = &self.maker.to_account_info();
+            let system_program = &self.system_program.to_account_info();
+            let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+            let spl_token_program = &self.token_program.to_account_info();
+            let spl_ata_program = &self.associated_token_program.to_account_info();
+
+            let transfer_cpi = TransferCpi::new(
+                &transfer_program,
+                TransferCpiAccounts {
+                    token,
+                    token_owner,
+                    destination_token,
+                    destination_owner,
+                    mint: nft,
+                    metadata,
+                    edition: Some(edition),
+                    token_record: None,
+                    destination_token_record: None,
+                    authority: token_owner,
+                    payer,
+                    system_program,
+                    sysvar_instructions,
+                    spl_token_program,
+                    spl_ata_program,
+                    authorization_rules_program: None,
+                    authorization_rules: None,
+                },
+                TransferInstructionArgs {
+                    transfer_args: TransferArgs::V1 {
+                        amount: 1,
+                        authorization_data: None,
+                    },
+                }
+            );
+
+            transfer_cpi.invoke()?;
+
+            offered_nfts.push(nft.key());
+        }
+
+        self.swap.set_inner(
+            SwapState {
+                maker: self.maker.key(),
+                seed,
+                offered_nfts,
+                amount,
+                wanted,
+            }
+        );
+
+        if amount > 0 {
+            let transfer_program = self.system_program.to_account_info();
+            let transfer_accounts = Transfer {
+                from: self.maker.to_account_info(),
+                to: self.bid_vault.to_account_info(),
+            };
+            let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
+
+            transfer(transfer_cpi, amount)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+pub use anchor_lang::prelude::*;
+
+use mpl_core::{
+    instructions::{
+        UpdatePluginV1Cpi, UpdatePluginV1CpiAccounts, UpdatePluginV1InstructionArgs,
+        RemovePluginV1Cpi, RemovePluginV1CpiAccounts, RemovePluginV1InstructionArgs,
+    },
+    types::{FreezeDelegate, Plugin, PluginType},
+};
+
+pub use crate::state::*;
+
+#[derive(Accounts)]
+pub struct DelistCore<'info> {
+    #[account(mut)]
+    pub lister: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        close = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), asset.key().as_ref()],
+        bump,
+        has_one = lister,
+        has_one = collection,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut, owner = mpl_core::ID)]
+    /// CHECK: checked by the listing seeds
+    pub asset: AccountInfo<'info>,
+    #[account(mut)]
+    /// CHECK: checked by the listing
+    pub collection: AccountInfo<'info>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: checked by address
+    pub core_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DelistCore<'info> {
+    pub fn delist_core(
+        &mut self,
+        bumps: DelistCoreBumps,
+    ) -> Result<()> {
+
+        let marketplace_key = self.marketplace.key();
+        let asset_key = self.asset.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            asset_key.as_ref(),
+            &[bumps.listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let core_program = &self.core_program.to_account_info();
+        let asset = &self.asset.to_account_info();
+        let collection = &self.collection.to_account_info();
+        let payer = &self.lister.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+
+        // Thaw the asset > Only the listing holds the Freeze delegate
+        let listing = &self.listing.to_account_info();
+
+        let thaw_cpi = UpdatePluginV1Cpi::new(
+            core_program,
+            UpdatePluginV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(listing),
+                system_program,
+                log_wrapper: None,
+            },
+            UpdatePluginV1InstructionArgs {
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: false }),
+            },
+        );
+
+        thaw_cpi.invoke_signed(signer_seeds)?;
+
+        // Remove both delegates as the owner of the asset
+        let authority = &self.lister.to_account_info();
+
+        for plugin_type in [PluginType::FreezeDelegate, PluginType::TransferDelegate] {
+            let remove_cpi = RemovePluginV1Cpi::new(
+                core_program,
+                RemovePluginV1CpiAccounts {
+                    asset,
+                    collection: Some(collection),
+                    payer,
+                    authority: Some(authority),
+                    system_program,
+                    log_wrapper: None,
+                },
+                RemovePluginV1InstructionArgs {
+                    plugin_type,
+                },
+            );
+
+            remove_cpi.invoke()?;
+        }
+
+        Ok(())
+    }
+}
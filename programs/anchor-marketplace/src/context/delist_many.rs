@@ -0,0 +1,143 @@
+pub use anchor_lang::prelude::*;
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::TokenInterface,
+    metadata::{Metadata,
+        mpl_token_metadata::instructions::{UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs, RevokeCpi, RevokeCpiAccounts, RevokeInstructionArgs},
+    },
+};
+use mpl_token_metadata::types::{RevokeArgs, UnlockArgs};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use super::list_many::BULK_LISTING_ACCOUNTS;
+
+#[derive(Accounts)]
+pub struct DelistMany<'info> {
+    #[account(mut)]
+    pub lister: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DelistMany<'info> {
+    pub fn delist_many(
+        &mut self,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let nfts = remaining_accounts.chunks_exact(BULK_LISTING_ACCOUNTS);
+        require!(nfts.len() > 0 && nfts.remainder().is_empty(), MarketplaceError::MissingBulkAccounts);
+
+        for accounts in nfts {
+            self.delist_one(accounts)?;
+        }
+
+        Ok(())
+    }
+
+    fn delist_one(
+        &self,
+        accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let [lister_ata, listing_info, nft, metadata, edition] = accounts else {
+            return err!(MarketplaceError::MissingBulkAccounts);
+        };
+
+        let marketplace_key = self.marketplace.key();
+        let nft_key = nft.key();
+        let (listing_key, listing_bump) = Pubkey::find_program_address(
+            &[b"listing", marketplace_key.as_ref(), nft_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(listing_info.key(), listing_key, ErrorCode::ConstraintSeeds);
+
+        let listing = Account::<Listing>::try_from(listing_info)?;
+        require_keys_eq!(listing.lister, self.lister.key(), ErrorCode::ConstraintHasOne);
+        require_keys_eq!(listing.nft, nft_key, ErrorCode::ConstraintHasOne);
+        require!(!listing.escrowed, MarketplaceError::EscrowNotSupported);
+
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[listing_bump]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let token_metadata_program = &self.token_metadata_program.to_account_info();
+        let token_owner = &self.lister.to_account_info();
+        let payer = &self.lister.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        let unlock_cpi = UnlockCpi::new(
+            token_metadata_program,
+            UnlockCpiAccounts {
+                authority: listing_info,
+                token_owner: Some(token_owner),
+                token: lister_ata,
+                mint: nft,
+                metadata,
+                edition: Some(edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            UnlockInstructionArgs {
+                unlock_args: UnlockArgs::V1 {
+                    authorization_data: None,
+                },
+            }
+        );
+
+        unlock_cpi.invoke_signed(signer_seeds)?;
+
+        let revoke_cpi = RevokeCpi::new(
+            token_metadata_program,
+            RevokeCpiAccounts {
+                delegate_record: None,
+                delegate: listing_info,
+                metadata,
+                master_edition: Some(edition),
+                token_record: None,
+                mint: nft,
+                token: Some(lister_ata),
+                authority: token_owner,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            RevokeInstructionArgs {
+                revoke_args: RevokeArgs::StandardV1,
+            },
+        );
+
+        revoke_cpi.invoke()?;
+
+        listing.close(self.lister.to_account_info())?;
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 pub use anchor_lang::prelude::*;
+pub use anchor_spl::token_interface::TokenInterface;
 
-pub use anchor_spl::token::Token;
 
 pub use crate::state::*;
+pub use crate::errors::*;
 
 #[derive(Accounts)]
 #[instruction(name: String)]
@@ -12,7 +13,7 @@ pub struct Initialize<'info> {
     #[account(
         init,
         payer = admin,
-        seeds = [b"marketplace", name.as_str().as_bytes(), admin.key().as_ref()],
+        seeds = [b"marketplace", name.as_bytes(), admin.key().as_ref()],
         bump,
         space = Marketplace::INIT_SPACE + name.len(),
     )]
@@ -22,7 +23,7 @@ pub struct Initialize<'info> {
         bump,
     )]
     pub fee_vault: SystemAccount<'info>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -32,11 +33,18 @@ impl<'info> Initialize<'info>{
         name: String,
         fee: u16,
     ) -> Result<()> {
+
+        require!(fee <= Marketplace::MAX_FEE, MarketplaceError::FeeTooHigh);
+
         self.marketplace.set_inner(
             Marketplace {
                 admin: self.admin.key(),
+                pending_admin: None,
+                creator: self.admin.key(),
                 fee,
+                payment_mints: vec![],
                 name,
+                escrow_listings: false,
             }
         );
 
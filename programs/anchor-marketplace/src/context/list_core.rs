@@ -0,0 +1,127 @@
+pub use anchor_lang::prelude::*;
+
+use mpl_core::{
+    accounts::BaseAssetV1,
+    instructions::{AddPluginV1Cpi, AddPluginV1CpiAccounts, AddPluginV1InstructionArgs},
+    types::{FreezeDelegate, Plugin, PluginAuthority, TransferDelegate, UpdateAuthority},
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+// Lists a Metaplex Core asset > The asset stays in the lister's wallet, frozen by a
+// Freeze delegate and movable by a Transfer delegate, both held by the listing.
+#[derive(Accounts)]
+pub struct ListCore<'info> {
+    #[account(mut)]
+    pub lister: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        init,
+        payer = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), asset.key().as_ref()],
+        bump,
+        space = Listing::INIT_SPACE,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut, owner = mpl_core::ID)]
+    /// CHECK: deserialized as a Core asset in the instruction
+    pub asset: AccountInfo<'info>,
+    #[account(mut, owner = mpl_core::ID)]
+    /// CHECK: checked against the asset's update authority
+    pub collection: AccountInfo<'info>,
+
+    #[account(address = mpl_core::ID)]
+    /// CHECK: checked by address
+    pub core_program: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ListCore<'info> {
+    pub fn list_core(
+        &mut self,
+        price: u64,
+        payment_mint: Option<Pubkey>,
+        expires_at: Option<i64>,
+        reserved_buyer: Option<Pubkey>,
+    ) -> Result<()> {
+
+        require_gt!(price, 0, MarketplaceError::InvalidAmount);
+        let asset = BaseAssetV1::try_from(&self.asset.to_account_info()).map_err(|_| MarketplaceError::InvalidAsset)?;
+        require_keys_eq!(asset.owner, self.lister.key(), MarketplaceError::InvalidAsset);
+        require!(asset.update_authority == UpdateAuthority::Collection(self.collection.key()), MarketplaceError::InvalidCollection);
+        if let Some(payment_mint) = payment_mint {
+            require!(self.marketplace.payment_mints.contains(&payment_mint), MarketplaceError::InvalidPaymentMint);
+        }
+        if let Some(expires_at) = expires_at {
+            require_gt!(expires_at, Clock::get()?.unix_timestamp, MarketplaceError::ListingExpired);
+        }
+
+        self.listing.set_inner(
+            Listing {
+                lister: self.lister.key(),
+                nft: self.asset.key(),
+                collection: self.collection.key(),
+                price,
+                payment_mint,
+                expires_at,
+                dutch_auction: None,
+                reserved_buyer,
+                escrowed: false,
+            }
+        );
+
+        let core_program = &self.core_program.to_account_info();
+        let asset = &self.asset.to_account_info();
+        let collection = &self.collection.to_account_info();
+        let authority = &self.lister.to_account_info();
+        let payer = &self.lister.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let listing = self.listing.key();
+
+        // Hand the Transfer delegate to the listing before freezing the asset
+        let add_transfer_delegate_cpi = AddPluginV1Cpi::new(
+            core_program,
+            AddPluginV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(authority),
+                system_program,
+                log_wrapper: None,
+            },
+            AddPluginV1InstructionArgs {
+                plugin: Plugin::TransferDelegate(TransferDelegate {}),
+                init_authority: Some(PluginAuthority::Address { address: listing }),
+            },
+        );
+
+        add_transfer_delegate_cpi.invoke()?;
+
+        let add_freeze_delegate_cpi = AddPluginV1Cpi::new(
+            core_program,
+            AddPluginV1CpiAccounts {
+                asset,
+                collection: Some(collection),
+                payer,
+                authority: Some(authority),
+                system_program,
+                log_wrapper: None,
+            },
+            AddPluginV1InstructionArgs {
+                plugin: Plugin::FreezeDelegate(FreezeDelegate { frozen: true }),
+                init_authority: Some(PluginAuthority::Address { address: listing }),
+            },
+        );
+
+        add_freeze_delegate_cpi.invoke()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+pub use anchor_lang::prelude::*;
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenInterface},
+    metadata::{Metadata, MetadataAccount,
+    mpl_token_metadata::{
+        instructions::{DelegateCpi, DelegateCpiAccounts, DelegateInstructionArgs, LockCpi, LockCpiAccounts, LockInstructionArgs},
+        types::{TokenStandard, Collection},
+    }},
+};
+use mpl_token_metadata::types::{DelegateArgs, LockArgs};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{check_metadata_accounts, check_nft_mint, create_pda_account};
+
+// Accounts passed for each NFT of `list_many` and `delist_many`: lister_ata, listing, nft, metadata, edition.
+// Only regular NFTs are handled in bulk, a pNFT still goes through `list` and `delist` with its token record.
+pub const BULK_LISTING_ACCOUNTS: usize = 5;
+
+#[derive(Accounts)]
+pub struct ListMany<'info> {
+    #[account(mut)]
+    pub lister: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    pub collection: InterfaceAccount<'info, Mint>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ListMany<'info> {
+    // One price for every NFT, or one price per NFT in the order they are passed
+    pub fn list_many(
+        &mut self,
+        prices: Vec<u64>,
+        payment_mint: Option<Pubkey>,
+        expires_at: Option<i64>,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let nfts = remaining_accounts.chunks_exact(BULK_LISTING_ACCOUNTS);
+        require!(nfts.len() > 0 && nfts.remainder().is_empty(), MarketplaceError::MissingBulkAccounts);
+        require!(prices.len() == 1 || prices.len() == nfts.len(), MarketplaceError::InvalidAmount);
+        require!(prices.iter().all(|price| *price > 0), MarketplaceError::InvalidAmount);
+        // Bulk listing only delegates and locks, escrowed listings go through `list` one at a time
+        require!(!self.marketplace.escrow_listings, MarketplaceError::EscrowNotSupported);
+        if let Some(payment_mint) = payment_mint {
+            require!(self.marketplace.payment_mints.contains(&payment_mint), MarketplaceError::InvalidPaymentMint);
+        }
+        if let Some(expires_at) = expires_at {
+            require_gt!(expires_at, Clock::get()?.unix_timestamp, MarketplaceError::ListingExpired);
+        }
+
+        for (i, accounts) in nfts.enumerate() {
+            let price = if prices.len() == 1 { prices[0] } else { prices[i] };
+            self.list_one(accounts, price, payment_mint, expires_at)?;
+        }
+
+        Ok(())
+    }
+
+    fn list_one(
+        &self,
+        accounts: &'info [AccountInfo<'info>],
+        price: u64,
+        payment_mint: Option<Pubkey>,
+        expires_at: Option<i64>,
+    ) -> Result<()> {
+
+        let [lister_ata, listing_info, nft, metadata_info, edition] = accounts else {
+            return err!(MarketplaceError::MissingBulkAccounts);
+        };
+
+        check_metadata_accounts(nft, metadata_info, edition)?;
+        check_nft_mint(nft)?;
+        let metadata = Account::<MetadataAccount>::try_from(metadata_info)?;
+        require!(metadata.token_standard == Some(TokenStandard::NonFungible), MarketplaceError::InvalidTokenStandard);
+        require!(metadata.collection == Some(Collection{verified: true, key: self.collection.key()}), MarketplaceError::InvalidCollection);
+
+        let marketplace_key = self.marketplace.key();
+        let nft_key = nft.key();
+        let (listing_key, listing_bump) = Pubkey::find_program_address(
+            &[b"listing", marketplace_key.as_ref(), nft_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(listing_info.key(), listing_key, ErrorCode::ConstraintSeeds);
+
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[listing_bump]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // Same as `init` on the listing > Fails if the NFT is already listed
+        create_pda_account(
+            &self.lister.to_account_info(),
+            listing_info,
+            Listing::INIT_SPACE,
+            &self.system_program.to_account_info(),
+            signer_seeds,
+        )?;
+
+        let mut listing = Account::<Listing>::try_from_unchecked(listing_info)?;
+        listing.set_inner(
+            Listing {
+                lister: self.lister.key(),
+                nft: nft_key,
+                collection: self.collection.key(),
+                price,
+                payment_mint,
+                expires_at,
+                dutch_auction: None,
+                reserved_buyer: None,
+                escrowed: false,
+            }
+        );
+        listing.exit(&crate::ID)?;
+
+        let token_metadata_program = &self.token_metadata_program.to_account_info();
+        let authority = &self.lister.to_account_info();
+        let payer = &self.lister.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        let delegate_cpi = DelegateCpi::new(
+            token_metadata_program,
+            DelegateCpiAccounts {
+                delegate_record: None,
+                delegate: listing_info,
+                metadata: metadata_info,
+                master_edition: Some(edition),
+                token_record: None,
+                mint: nft,
+                token: Some(lister_ata),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            DelegateInstructionArgs {
+                delegate_args: DelegateArgs::StandardV1 {
+                    amount: 1,
+                },
+            },
+        );
+
+        delegate_cpi.invoke()?;
+
+        let lock_cpi = LockCpi::new(
+            token_metadata_program,
+            LockCpiAccounts {
+                authority: listing_info,
+                token_owner: Some(authority),
+                token: lister_ata,
+                mint: nft,
+                metadata: metadata_info,
+                edition: Some(edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            LockInstructionArgs {
+                lock_args: LockArgs::V1 {
+                    authorization_data: None,
+                },
+            },
+        );
+
+        lock_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+}
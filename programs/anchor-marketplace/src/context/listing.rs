@@ -2,7 +2,7 @@ pub use anchor_lang::prelude::*;
 pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
 
 use anchor_spl::{
-    token::{Mint, TokenAccount}, 
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked, transfer_checked}, 
     metadata::{Metadata, MetadataAccount, MasterEditionAccount,
     mpl_token_metadata::{
         instructions::{DelegateCpi, DelegateCpiAccounts, DelegateInstructionArgs, LockCpi, LockCpiAccounts, LockInstructionArgs},
@@ -10,11 +10,11 @@ use anchor_spl::{
     }}, 
     associated_token::AssociatedToken
 };
-pub use anchor_spl::token::Token;
 use mpl_token_metadata::types::{DelegateArgs, LockArgs };
 
 pub use crate::state::*;
 pub use crate::errors::*;
+use crate::utils::check_nft_mint;
 
 #[derive(Accounts)]
 pub struct List<'info> {
@@ -25,36 +25,65 @@ pub struct List<'info> {
         payer = lister,
         associated_token::mint = nft,
         associated_token::authority = lister,
+        associated_token::token_program = token_program,
     )]
-    pub lister_ata: Account<'info, TokenAccount>,
+    pub lister_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes(), marketplace.admin.key().as_ref()],
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
         bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
     #[account(
         init,
         payer = lister,
-        seeds = [b"listing", marketplace.key().as_ref()],
+        seeds = [b"listing", marketplace.key().as_ref(), nft.key().as_ref()],
         bump,
         space = Listing::INIT_SPACE,
     )]
     pub listing: Account<'info, Listing>,
+    // Only needed when the marketplace escrows listings
+    #[account(
+        init_if_needed,
+        payer = lister,
+        associated_token::mint = nft,
+        associated_token::authority = listing,
+        associated_token::token_program = token_program,
+    )]
+    pub listing_vault: Option<InterfaceAccount<'info, TokenAccount>>,
 
-    pub collection: Account<'info, Mint>,
-    #[account(mut)]
-    pub nft: Account<'info, Mint>,
+    pub collection: InterfaceAccount<'info, Mint>,
     #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
     pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
     pub edition: Account<'info, MasterEditionAccount>,
 
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+
     #[account(address = INSTRUCTIONS_ID)]
     /// CHECK: no need to check it out
     pub sysvar_instruction: AccountInfo<'info>,
     pub token_metadata_program: Program<'info, Metadata>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -62,11 +91,27 @@ impl<'info> List<'info> {
     pub fn list(
         &mut self,
         price: u64,
+        payment_mint: Option<Pubkey>,
+        expires_at: Option<i64>,
+        reserved_buyer: Option<Pubkey>,
         bumps: ListBumps,
     ) -> Result<()> {
 
-        require!(self.metadata.token_standard.clone().unwrap() == TokenStandard::NonFungible, MarketplaceError::InvalidTokenStandard);
+        require_gt!(price, 0, MarketplaceError::InvalidAmount);
+        check_nft_mint(&self.nft.to_account_info())?;
+        let is_programmable = match self.metadata.token_standard {
+            Some(TokenStandard::NonFungible) => false,
+            Some(TokenStandard::ProgrammableNonFungible) => true,
+            _ => return err!(MarketplaceError::InvalidTokenStandard),
+        };
         require!(self.metadata.collection.clone().unwrap() == Collection{verified: true, key: self.collection.key()}, MarketplaceError::InvalidCollection); 
+        if let Some(payment_mint) = payment_mint {
+            require!(self.marketplace.payment_mints.contains(&payment_mint), MarketplaceError::InvalidPaymentMint);
+        }
+        if let Some(expires_at) = expires_at {
+            require_gt!(expires_at, Clock::get()?.unix_timestamp, MarketplaceError::ListingExpired);
+        }
+        let escrowed = self.marketplace.escrow_listings && !is_programmable;
 
         self.listing.set_inner(
             Listing {
@@ -74,10 +119,33 @@ impl<'info> List<'info> {
                 nft: self.nft.key(),
                 collection: self.collection.key(),
                 price,
+                payment_mint,
+                expires_at,
+                dutch_auction: None,
+                reserved_buyer,
+                escrowed,
             }
         );
 
-        let transfer_program = &self.token_program.to_account_info();
+        // Escrow mode > The NFT moves into the listing vault, so there is nothing to delegate or lock
+        if escrowed {
+            let Some(listing_vault) = self.listing_vault.as_ref() else {
+                return err!(MarketplaceError::MissingListingVault);
+            };
+
+            let transfer_program = self.token_program.to_account_info();
+            let transfer_accounts = TransferChecked {
+                from: self.lister_ata.to_account_info(),
+                mint: self.nft.to_account_info(),
+                to: listing_vault.to_account_info(),
+                authority: self.lister.to_account_info(),
+            };
+            let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
+
+            return transfer_checked(transfer_cpi, 1, self.nft.decimals);
+        }
+
+        let token_metadata_program = &self.token_metadata_program.to_account_info();
         let delegate = &self.listing.to_account_info();
         let metadata = &self.metadata.to_account_info();
         let master_edition = &self.edition.to_account_info();
@@ -88,15 +156,31 @@ impl<'info> List<'info> {
         let system_program = &self.system_program.to_account_info();
         let sysvar_instructions = &self.sysvar_instruction.to_account_info();
         let spl_token_program = &self.token_program.to_account_info();
+        let token_record = self.token_record.as_ref();
+        let authorization_rules = self.authorization_rules.as_ref();
+        let authorization_rules_program = self.authorization_rules_program.as_ref();
+
+        // A pNFT is frozen by the Sale delegate itself, while a regular NFT gets a Standard delegate that we lock
+        let delegate_args = if is_programmable {
+            require!(token_record.is_some(), MarketplaceError::MissingTokenRecord);
+            DelegateArgs::SaleV1 {
+                amount: 1,
+                authorization_data: None,
+            }
+        } else {
+            DelegateArgs::StandardV1 {
+                amount: 1,
+            }
+        };
 
         let delegate_cpi = DelegateCpi::new(
-            transfer_program,
+            token_metadata_program,
             DelegateCpiAccounts {
                 delegate_record: None,
                 delegate,
                 metadata,
                 master_edition: Some(master_edition),
-                token_record: None,
+                token_record,
                 mint,
                 token: Some(token),
                 authority,
@@ -104,24 +188,26 @@ impl<'info> List<'info> {
                 system_program,
                 sysvar_instructions,
                 spl_token_program: Some(spl_token_program),
-                authorization_rules_program: None,
-                authorization_rules: None,
+                authorization_rules_program,
+                authorization_rules,
                 
             },
             DelegateInstructionArgs {
-                delegate_args: DelegateArgs::StandardV1 {
-                    amount: 1, 
-                },
+                delegate_args,
             },
         );
 
         delegate_cpi.invoke()?;
 
+        if is_programmable {
+            return Ok(());
+        }
+
         let authority = &self.listing.to_account_info();
         let token_owner = &self.lister.to_account_info();
         
         let lock_cpi = LockCpi::new(
-            transfer_program,
+            token_metadata_program,
             LockCpiAccounts {
                 authority,
                 token_owner: Some(token_owner),
@@ -145,9 +231,11 @@ impl<'info> List<'info> {
         );
 
         let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
         let seed = &[
             b"listing",
             marketplace_key.as_ref(),
+            nft_key.as_ref(),
             &[bumps.listing]
         ];
         let signer_seeds = &[&seed[..]];
@@ -156,4 +244,26 @@ impl<'info> List<'info> {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Starts at `start_price` and decays to `end_price` > The listing never expires, it just stays at the floor
+    pub fn list_dutch(
+        &mut self,
+        dutch_auction: DutchAuction,
+        payment_mint: Option<Pubkey>,
+        reserved_buyer: Option<Pubkey>,
+        bumps: ListBumps,
+    ) -> Result<()> {
+
+        require!(dutch_auction.start_price > dutch_auction.end_price && dutch_auction.end_price > 0, MarketplaceError::InvalidAmount);
+        require_gt!(dutch_auction.end_time, dutch_auction.start_time, MarketplaceError::InvalidAuctionTimes);
+        require_gte!(dutch_auction.start_time, 0, MarketplaceError::InvalidAuctionTimes);
+        if let PriceDecay::Exponential { half_life } = dutch_auction.decay {
+            require_gt!(half_life, 0, MarketplaceError::InvalidAuctionTimes);
+        }
+
+        self.list(dutch_auction.start_price, payment_mint, None, reserved_buyer, bumps)?;
+        self.listing.dutch_auction = Some(dutch_auction);
+
+        Ok(())
+    }
+}
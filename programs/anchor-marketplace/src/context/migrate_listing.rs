@@ -0,0 +1,259 @@
+pub use anchor_lang::{prelude::*, Discriminator};
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::instructions::{
+        UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs, RevokeCpi, RevokeCpiAccounts, RevokeInstructionArgs,
+        DelegateCpi, DelegateCpiAccounts, DelegateInstructionArgs, LockCpi, LockCpiAccounts, LockInstructionArgs,
+    }},
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::{DelegateArgs, LockArgs, RevokeArgs, UnlockArgs};
+
+pub use crate::state::*;
+
+// Moves a listing created with the old `[b"listing", marketplace]` seeds to the
+// per-mint `[b"listing", marketplace, nft]` PDA, handing the delegate and lock over
+// to the new account so the NFT never leaves the lister's wallet unlocked.
+#[derive(Accounts)]
+pub struct MigrateListing<'info> {
+    #[account(mut)]
+    pub lister: Signer<'info>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = lister,
+        associated_token::token_program = token_program,
+    )]
+    pub lister_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"listing", marketplace.key().as_ref()],
+        bump,
+        owner = crate::ID,
+    )]
+    /// CHECK: deserialized by hand since the legacy layout predates the newer Listing fields
+    pub legacy_listing: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = lister,
+        seeds = [b"listing", marketplace.key().as_ref(), nft.key().as_ref()],
+        bump,
+        space = Listing::INIT_SPACE,
+    )]
+    pub listing: Account<'info, Listing>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateListing<'info> {
+    pub fn migrate_listing(
+        &mut self,
+        bumps: MigrateListingBumps,
+    ) -> Result<()> {
+
+        let legacy = {
+            let data = self.legacy_listing.try_borrow_data()?;
+            require!(data.len() >= 8 && data[..8] == Listing::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+            LegacyListing::deserialize(&mut &data[8..])?
+        };
+
+        require_keys_eq!(legacy.lister, self.lister.key(), ErrorCode::ConstraintHasOne);
+        require_keys_eq!(legacy.nft, self.nft.key(), ErrorCode::ConstraintHasOne);
+
+        self.listing.set_inner(
+            Listing {
+                lister: legacy.lister,
+                nft: legacy.nft,
+                collection: legacy.collection,
+                price: legacy.price,
+                payment_mint: None,
+                expires_at: None,
+                dutch_auction: None,
+                reserved_buyer: None,
+                escrowed: false,
+            }
+        );
+
+        let token_metadata_program = &self.token_metadata_program.to_account_info();
+        let token_owner = &self.lister.to_account_info();
+        let token = &self.lister_ata.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let payer = &self.lister.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        // Unlock and revoke the legacy listing
+        let legacy_listing = &self.legacy_listing.to_account_info();
+
+        let unlock_cpi = UnlockCpi::new(
+            token_metadata_program,
+            UnlockCpiAccounts {
+                authority: legacy_listing,
+                token_owner: Some(token_owner),
+                token,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            UnlockInstructionArgs {
+                unlock_args: UnlockArgs::V1 {
+                    authorization_data: None,
+                },
+            }
+        );
+
+        let marketplace_key = self.marketplace.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            &[bumps.legacy_listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // A listing whose lock was already lifted only needs its delegate revoked
+        if self.lister_ata.is_frozen() {
+            unlock_cpi.invoke_signed(signer_seeds)?;
+        }
+
+        let revoke_cpi = RevokeCpi::new(
+            token_metadata_program,
+            RevokeCpiAccounts {
+                delegate_record: None,
+                delegate: legacy_listing,
+                metadata,
+                master_edition: Some(edition),
+                token_record: None,
+                mint,
+                token: Some(token),
+                authority: token_owner,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            RevokeInstructionArgs {
+                revoke_args: RevokeArgs::StandardV1
+            },
+        );
+
+        revoke_cpi.invoke()?;
+
+        // Delegate and lock with the per-mint listing
+        let listing = &self.listing.to_account_info();
+
+        let delegate_cpi = DelegateCpi::new(
+            token_metadata_program,
+            DelegateCpiAccounts {
+                delegate_record: None,
+                delegate: listing,
+                metadata,
+                master_edition: Some(edition),
+                token_record: None,
+                mint,
+                token: Some(token),
+                authority: token_owner,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            DelegateInstructionArgs {
+                delegate_args: DelegateArgs::StandardV1 {
+                    amount: 1,
+                },
+            },
+        );
+
+        delegate_cpi.invoke()?;
+
+        let lock_cpi = LockCpi::new(
+            token_metadata_program,
+            LockCpiAccounts {
+                authority: listing,
+                token_owner: Some(token_owner),
+                token,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            LockInstructionArgs {
+                lock_args: LockArgs::V1 {
+                    authorization_data: None,
+                },
+            },
+        );
+
+        let nft_key = self.nft.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[bumps.listing]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        lock_cpi.invoke_signed(signer_seeds)?;
+
+        // Close the legacy listing and give the rent back to the lister
+        let legacy_listing = self.legacy_listing.to_account_info();
+        let lamports = legacy_listing.lamports();
+        **legacy_listing.try_borrow_mut_lamports()? = 0;
+        **self.lister.try_borrow_mut_lamports()? += lamports;
+        legacy_listing.assign(&System::id());
+        legacy_listing.realloc(0, false)?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
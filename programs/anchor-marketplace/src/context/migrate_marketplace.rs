@@ -0,0 +1,74 @@
+pub use anchor_lang::{prelude::*, Discriminator};
+use anchor_lang::system_program::{transfer, Transfer};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+// Rewrites a marketplace created before admin handover, payment mints and listing modes.
+// The legacy `[b"marketplace", name, admin]` PDA is the address the current seeds derive
+// once the admin is recorded as the creator, so the account is grown in place.
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct MigrateMarketplace<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"marketplace", name.as_bytes(), admin.key().as_ref()],
+        bump,
+        owner = crate::ID,
+    )]
+    /// CHECK: deserialized by hand since the legacy layout predates the newer Marketplace fields
+    pub marketplace: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateMarketplace<'info> {
+    pub fn migrate_marketplace(
+        &mut self,
+        name: String,
+    ) -> Result<()> {
+
+        let legacy = {
+            let data = self.marketplace.try_borrow_data()?;
+            require!(data.len() >= 8 && data[..8] == Marketplace::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+            require_eq!(data.len(), LegacyMarketplace::SIZE + name.len(), MarketplaceError::MarketplaceAlreadyMigrated);
+            LegacyMarketplace::deserialize(&mut &data[8..])?
+        };
+
+        require_keys_eq!(legacy.admin, self.admin.key(), ErrorCode::ConstraintHasOne);
+
+        // Top up the rent for the larger layout before growing the account
+        let space = Marketplace::INIT_SPACE + name.len();
+        let rent = Rent::get()?.minimum_balance(space);
+        let lamports = self.marketplace.lamports();
+
+        if rent > lamports {
+            let cpi_program = self.system_program.to_account_info();
+            let cpi_accounts = Transfer {
+                from: self.admin.to_account_info(),
+                to: self.marketplace.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+            transfer(cpi_ctx, rent - lamports)?;
+        }
+
+        self.marketplace.realloc(space, true)?;
+
+        let marketplace = Marketplace {
+            admin: legacy.admin,
+            pending_admin: None,
+            creator: legacy.admin,
+            // Legacy marketplaces had no cap on the fee
+            fee: legacy.fee.min(Marketplace::MAX_FEE),
+            payment_mints: vec![],
+            name: legacy.name,
+            escrow_listings: false,
+        };
+
+        let mut data = self.marketplace.try_borrow_mut_data()?;
+        marketplace.try_serialize(&mut &mut data[..])?;
+
+        Ok(())
+    }
+}
@@ -6,6 +6,42 @@ pub mod bid;
 pub mod accept_bid;
 pub mod cancel_bid;
 pub mod modify_bid;
+pub mod migrate_listing;
+pub mod migrate_marketplace;
+pub mod update_marketplace;
+pub mod propose_admin;
+pub mod accept_admin;
+pub mod withdraw_fees;
+pub mod update_listing;
+pub mod close_expired_listing;
+pub mod list_core;
+pub mod delist_core;
+pub mod buy_core;
+pub mod accept_bid_core;
+pub mod close_expired_listing_core;
+pub mod collection_bid;
+pub mod accept_collection_bid;
+pub mod cancel_collection_bid;
+pub mod offer;
+pub mod accept_offer;
+pub mod cancel_offer;
+pub mod trait_bid;
+pub mod accept_trait_bid;
+pub mod cancel_trait_bid;
+pub mod create_auction;
+pub mod place_auction_bid;
+pub mod settle_auction;
+pub mod sweep;
+pub mod list_many;
+pub mod delist_many;
+pub mod create_swap;
+pub mod accept_swap;
+pub mod cancel_swap;
+pub mod create_pool;
+pub mod pool_sol;
+pub mod pool_nft;
+pub mod buy_from_pool;
+pub mod sell_to_pool;
 
 pub use init::*;
 pub use listing::*;
@@ -14,4 +50,40 @@ pub use buy::*;
 pub use bid::*;
 pub use accept_bid::*;
 pub use cancel_bid::*;
-pub use modify_bid::*;
\ No newline at end of file
+pub use modify_bid::*;
+pub use migrate_listing::*;
+pub use migrate_marketplace::*;
+pub use update_marketplace::*;
+pub use propose_admin::*;
+pub use accept_admin::*;
+pub use withdraw_fees::*;
+pub use update_listing::*;
+pub use close_expired_listing::*;
+pub use list_core::*;
+pub use delist_core::*;
+pub use buy_core::*;
+pub use accept_bid_core::*;
+pub use close_expired_listing_core::*;
+pub use collection_bid::*;
+pub use accept_collection_bid::*;
+pub use cancel_collection_bid::*;
+pub use offer::*;
+pub use accept_offer::*;
+pub use cancel_offer::*;
+pub use trait_bid::*;
+pub use accept_trait_bid::*;
+pub use cancel_trait_bid::*;
+pub use create_auction::*;
+pub use place_auction_bid::*;
+pub use settle_auction::*;
+pub use sweep::*;
+pub use list_many::*;
+pub use delist_many::*;
+pub use create_swap::*;
+pub use accept_swap::*;
+pub use cancel_swap::*;
+pub use create_pool::*;
+pub use pool_sol::*;
+pub use pool_nft::*;
+pub use buy_from_pool::*;
+pub use sell_to_pool::*;
\ No newline at end of file
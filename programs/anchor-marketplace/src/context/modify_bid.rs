@@ -3,7 +3,7 @@ pub use anchor_lang::{
     system_program::{Transfer, transfer}
 };
 
-use anchor_spl::token::Token;
+use anchor_spl::token_interface::{Mint, TokenAccount, TransferChecked, transfer_checked, TokenInterface};
 
 pub use crate::state::*;
 pub use crate::errors::*;
@@ -14,13 +14,13 @@ pub struct ModifyBid<'info> {
     pub bidder: Signer<'info>,
 
     #[account(
-        seeds = [b"marketplace", marketplace.name.as_str().as_bytes(), marketplace.admin.key().as_ref()],
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
         bump,
     )]
     pub marketplace: Account<'info, Marketplace>,
+    // Modifying a bid on a bought or delisted listing fails here since the account is gone
     #[account(
-        mut, 
-        seeds = [b"listing", marketplace.key().as_ref()],
+        seeds = [b"listing", marketplace.key().as_ref(), listing.nft.as_ref()],
         bump,
     )]
     pub listing: Account<'info, Listing>,
@@ -32,12 +32,30 @@ pub struct ModifyBid<'info> {
     )]
     pub bid: Account<'info, BidState>,
     #[account(
-        seeds = [b"listing_vault", bid.key().as_ref()],
+        mut,
+        seeds = [b"bid_vault", bid.key().as_ref()],
         bump,
     )]
     pub bid_vault: SystemAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -50,42 +68,89 @@ impl<'info> ModifyBid<'info> {
 
         require!(amount > 0 && amount != self.bid.price, MarketplaceError::InvalidAmount);
 
-        if amount > self.bid.price {
-
-            let transfer_program = self.system_program.to_account_info();
-            let transfer_accounts = Transfer {
-                from: self.bidder.to_account_info(),
-                to: self.bid_vault.to_account_info(),
-            };
-            let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
-
-            transfer(transfer_cpi, amount-self.bid.price)?;
-
-            self.bid.price = amount;
-
-        } else {
-
-            let transfer_program = self.system_program.to_account_info();
-            let transfer_accounts = Transfer {
-                from: self.bidder.to_account_info(),
-                to: self.bid_vault.to_account_info(),
-            };
-
-            let bid_key = self.bid.key();
-            let seed = &[
-                b"listing_vault",
-                bid_key.as_ref(),
-                &[bumps.bid]
-            ];
-            let signer_seeds = &[&seed[..]];
-
-            let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
-
-            transfer(transfer_cpi, self.bid.price-amount)?;
-
-            self.bid.price = amount;
-            
+        let bid_key = self.bid.key();
+        let seed = &[
+            b"bid_vault",
+            bid_key.as_ref(),
+            &[bumps.bid_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        match self.bid.payment_mint {
+            None => {
+                if amount > self.bid.price {
+
+                    let transfer_program = self.system_program.to_account_info();
+                    let transfer_accounts = Transfer {
+                        from: self.bidder.to_account_info(),
+                        to: self.bid_vault.to_account_info(),
+                    };
+                    let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
+
+                    transfer(transfer_cpi, amount-self.bid.price)?;
+
+                } else {
+
+                    // Refund the difference from the bid vault
+                    let transfer_program = self.system_program.to_account_info();
+                    let transfer_accounts = Transfer {
+                        from: self.bid_vault.to_account_info(),
+                        to: self.bidder.to_account_info(),
+                    };
+                    let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+                    transfer(transfer_cpi, self.bid.price-amount)?;
+
+                }
+
+                // Anyone can send lamports to the vault, so only check that it covers the new price
+                require_gte!(self.bid_vault.lamports(), amount, MarketplaceError::BidVaultMismatch);
+            }
+            Some(payment_mint) => {
+                let (Some(mint), Some(bidder_payment_ata), Some(bid_vault_payment_ata)) = (
+                    self.payment_mint.as_ref(),
+                    self.bidder_payment_ata.as_ref(),
+                    self.bid_vault_payment_ata.as_mut(),
+                ) else {
+                    return err!(MarketplaceError::MissingPaymentAccounts);
+                };
+                require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+                if amount > self.bid.price {
+
+                    let transfer_program = self.token_program.to_account_info();
+                    let transfer_accounts = TransferChecked {
+                        from: bidder_payment_ata.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: bid_vault_payment_ata.to_account_info(),
+                        authority: self.bidder.to_account_info(),
+                    };
+                    let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
+
+                    transfer_checked(transfer_cpi, amount-self.bid.price, mint.decimals)?;
+
+                } else {
+
+                    // Refund the difference from the bid vault
+                    let transfer_program = self.token_program.to_account_info();
+                    let transfer_accounts = TransferChecked {
+                        from: bid_vault_payment_ata.to_account_info(),
+                        mint: mint.to_account_info(),
+                        to: bidder_payment_ata.to_account_info(),
+                        authority: self.bid_vault.to_account_info(),
+                    };
+                    let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+                    transfer_checked(transfer_cpi, self.bid.price-amount, mint.decimals)?;
+
+                }
+
+                bid_vault_payment_ata.reload()?;
+                require_gte!(bid_vault_payment_ata.amount, amount, MarketplaceError::BidVaultMismatch);
+            }
         }
+
+        self.bid.price = amount;
         
         Ok(())
     }
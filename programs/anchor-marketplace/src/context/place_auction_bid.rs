@@ -0,0 +1,97 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+pub struct PlaceAuctionBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    // Only needed once the auction has a highest bidder, who gets refunded here.
+    // Not a SystemAccount, so a bidder who reassigns their wallet can't stop others from outbidding them.
+    #[account(mut)]
+    /// CHECK: checked against the auction's highest bidder, it only receives lamports
+    pub previous_bidder: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"auction", marketplace.key().as_ref(), auction.nft.as_ref()],
+        bump,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"auction_vault", auction.key().as_ref()],
+        bump,
+    )]
+    pub auction_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PlaceAuctionBid<'info> {
+    pub fn place_auction_bid(
+        &mut self,
+        amount: u64,
+        bumps: PlaceAuctionBidBumps,
+    ) -> Result<()> {
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(self.auction.is_live(now), MarketplaceError::AuctionNotLive);
+        let min_bid = self.auction.min_bid().ok_or(MarketplaceError::InvalidAmount)?;
+        require_gte!(amount, min_bid, MarketplaceError::BidTooLow);
+
+        // Escrow the new bid
+        let transfer_program = self.system_program.to_account_info();
+        let transfer_accounts = Transfer {
+            from: self.bidder.to_account_info(),
+            to: self.auction_vault.to_account_info(),
+        };
+        let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
+
+        transfer(transfer_cpi, amount)?;
+
+        // Refund the bidder we just outbid
+        if let Some(highest_bidder) = self.auction.highest_bidder {
+            let Some(previous_bidder) = self.previous_bidder.as_ref() else {
+                return err!(MarketplaceError::InvalidBidder);
+            };
+            require_keys_eq!(previous_bidder.key(), highest_bidder, MarketplaceError::InvalidBidder);
+
+            let auction_key = self.auction.key();
+            let seed = &[
+                b"auction_vault",
+                auction_key.as_ref(),
+                &[bumps.auction_vault]
+            ];
+            let signer_seeds = &[&seed[..]];
+
+            let transfer_program = self.system_program.to_account_info();
+            let transfer_accounts = Transfer {
+                from: self.auction_vault.to_account_info(),
+                to: previous_bidder.to_account_info(),
+            };
+            let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+            transfer(transfer_cpi, self.auction.highest_bid)?;
+        }
+
+        self.auction.highest_bidder = Some(self.bidder.key());
+        self.auction.highest_bid = amount;
+
+        // A bid in the last stretch pushes the end back so nobody can snipe the auction
+        if self.auction.end_time - now < self.auction.extension_window {
+            self.auction.end_time = now.checked_add(self.auction.extension_window).ok_or(MarketplaceError::InvalidAuctionTimes)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, CloseAccount, close_account, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+        types::{TokenStandard, Collection},
+    }},
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::TransferArgs;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::check_nft_mint;
+
+#[derive(Accounts)]
+pub struct PoolNft<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = nft,
+        associated_token::authority = owner,
+        associated_token::token_program = token_program,
+    )]
+    pub owner_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = nft,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"pool", marketplace.key().as_ref(), owner.key().as_ref(), pool.seed.to_le_bytes().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PoolNft<'info> {
+    pub fn deposit_pool_nft(
+        &mut self,
+    ) -> Result<()> {
+
+        match self.metadata.token_standard {
+            Some(TokenStandard::NonFungible) | Some(TokenStandard::ProgrammableNonFungible) => {},
+            _ => return err!(MarketplaceError::InvalidTokenStandard),
+        }
+        check_nft_mint(&self.nft.to_account_info())?;
+        require!(self.metadata.collection == Some(Collection{verified: true, key: self.pool.collection}), MarketplaceError::InvalidCollection);
+
+        // Move the NFT into the pool > The owner signs, so no delegate is involved
+        let transfer_program = self.token_metadata_program.to_account_info();
+        let token = &self.owner_ata.to_account_info();
+        let token_owner = &self.owner.to_account_info();
+        let destination_token = &self.pool_ata.to_account_info();
+        let destination_owner = &self.pool.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let authority = &self.owner.to_account_info();
+        let payer = &self.owner.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            &transfer_program,
+            TransferCpiAccounts {
+                token,
+                token_owner,
+                destination_token,
+                destination_owner,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke()?;
+
+        self.pool.nft_count = self.pool.nft_count.checked_add(1).ok_or(MarketplaceError::InvalidAmount)?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_pool_nft(
+        &mut self,
+        bumps: PoolNftBumps,
+    ) -> Result<()> {
+
+        let marketplace_key = self.marketplace.key();
+        let owner_key = self.owner.key();
+        let pool_seed = self.pool.seed.to_le_bytes();
+        let seed = &[
+            b"pool",
+            marketplace_key.as_ref(),
+            owner_key.as_ref(),
+            pool_seed.as_ref(),
+            &[bumps.pool]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // Move the NFT back to the owner > The pool signs as the owner of its token account
+        let transfer_program = self.token_metadata_program.to_account_info();
+        let token = &self.pool_ata.to_account_info();
+        let token_owner = &self.pool.to_account_info();
+        let destination_token = &self.owner_ata.to_account_info();
+        let destination_owner = &self.owner.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let payer = &self.owner.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            &transfer_program,
+            TransferCpiAccounts {
+                token,
+                token_owner,
+                destination_token,
+                destination_owner,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
+                authority: token_owner,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke_signed(signer_seeds)?;
+
+        self.pool.nft_count = self.pool.nft_count.checked_sub(1).ok_or(MarketplaceError::InvalidAmount)?;
+
+        // A pNFT's token account stays frozen, so only a regular NFT's empty account can be closed
+        if self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible) {
+            return Ok(());
+        }
+
+        let close_program = self.token_program.to_account_info();
+        let close_accounts = CloseAccount {
+            account: self.pool_ata.to_account_info(),
+            destination: self.owner.to_account_info(),
+            authority: self.pool.to_account_info(),
+        };
+        let close_cpi = CpiContext::new_with_signer(close_program, close_accounts, signer_seeds);
+
+        close_account(close_cpi)
+    }
+}
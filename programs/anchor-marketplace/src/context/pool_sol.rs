@@ -0,0 +1,82 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+pub struct PoolSol<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        seeds = [b"pool", marketplace.key().as_ref(), owner.key().as_ref(), pool.seed.to_le_bytes().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"pool_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_vault: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> PoolSol<'info> {
+    pub fn deposit_pool_sol(
+        &mut self,
+        amount: u64,
+    ) -> Result<()> {
+
+        require!(amount > 0, MarketplaceError::InvalidAmount);
+
+        let transfer_program = self.system_program.to_account_info();
+        let transfer_accounts = Transfer {
+            from: self.owner.to_account_info(),
+            to: self.pool_vault.to_account_info(),
+        };
+        let transfer_cpi = CpiContext::new(transfer_program, transfer_accounts);
+
+        transfer(transfer_cpi, amount)
+    }
+
+    pub fn withdraw_pool_sol(
+        &mut self,
+        amount: Option<u64>,
+        bumps: PoolSolBumps,
+    ) -> Result<()> {
+
+        // Without an amount the vault is emptied, rent included, since the owner is done with it
+        let available = self.pool_vault.lamports();
+        let amount = amount.unwrap_or(available);
+
+        require!(amount > 0 && amount <= available, MarketplaceError::InvalidAmount);
+
+        let pool_key = self.pool.key();
+        let seed = &[
+            b"pool_vault",
+            pool_key.as_ref(),
+            &[bumps.pool_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let transfer_program = self.system_program.to_account_info();
+        let transfer_accounts = Transfer {
+            from: self.pool_vault.to_account_info(),
+            to: self.owner.to_account_info(),
+        };
+        let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+        transfer(transfer_cpi, amount)
+    }
+}
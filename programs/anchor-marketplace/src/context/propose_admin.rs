@@ -0,0 +1,28 @@
+pub use anchor_lang::prelude::*;
+
+pub use crate::state::*;
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+        has_one = admin,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+}
+
+impl<'info> ProposeAdmin<'info> {
+    pub fn propose_admin(
+        &mut self,
+        new_admin: Option<Pubkey>,
+    ) -> Result<()> {
+
+        // Passing None cancels a pending handover
+        self.marketplace.pending_admin = new_admin;
+
+        Ok(())
+    }
+}
\ No newline at end of file
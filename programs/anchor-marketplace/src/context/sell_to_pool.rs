@@ -0,0 +1,211 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+        types::{TokenStandard, Collection},
+    }},
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::TransferArgs;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{creator_royalties, amount_after_fees, pay_sale_in_sol, SolPayment, check_nft_mint};
+
+#[derive(Accounts)]
+pub struct SellToPool<'info> {
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    pub owner: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program,
+    )]
+    pub seller_ata: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = nft,
+        associated_token::authority = pool,
+        associated_token::token_program = token_program,
+    )]
+    pub pool_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"pool", marketplace.key().as_ref(), owner.key().as_ref(), pool.seed.to_le_bytes().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub pool: Account<'info, Pool>,
+    #[account(
+        mut,
+        seeds = [b"pool_vault", pool.key().as_ref()],
+        bump,
+    )]
+    pub pool_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SellToPool<'info> {
+    pub fn sell_to_pool(
+        &mut self,
+        min_price: u64,
+        bumps: SellToPoolBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        require!(self.pool.kind != PoolKind::Nft, MarketplaceError::InvalidPoolSide);
+        match self.metadata.token_standard {
+            Some(TokenStandard::NonFungible) | Some(TokenStandard::ProgrammableNonFungible) => {},
+            _ => return err!(MarketplaceError::InvalidTokenStandard),
+        }
+        check_nft_mint(&self.nft.to_account_info())?;
+        require!(self.metadata.collection == Some(Collection{verified: true, key: self.pool.collection}), MarketplaceError::InvalidCollection);
+
+        // The price moves down one step with every NFT the pool buys
+        let price = self.pool.buy_price();
+        require_gte!(price, min_price, MarketplaceError::PriceBelowMin);
+        // Once the curve bottoms out the pool stops buying, it never takes NFTs for free
+        let spot_price = self.pool.curve.step_down(self.pool.spot_price);
+        require!(price > 0 && spot_price > 0, MarketplaceError::InvalidPoolPrice);
+
+        // The vault keeps its rent-exempt minimum while the pool is trading
+        let rent_exempt = Rent::get()?.minimum_balance(0);
+        let available = self.pool_vault.lamports().saturating_sub(rent_exempt);
+        require_gte!(available, price, MarketplaceError::InsufficientPoolFunds);
+
+        self.pool.spot_price = spot_price;
+        self.pool.nft_count = self.pool.nft_count.checked_add(1).ok_or(MarketplaceError::InvalidAmount)?;
+
+        // Same split as a sale: Fee and Royalties come out of the price
+        let fee = self.marketplace.fee_amount(price);
+        let royalties = creator_royalties(&self.metadata, price);
+        let seller_amount = amount_after_fees(price, fee, &royalties)?;
+
+        let pool_key = self.pool.key();
+        let seed = &[
+            b"pool_vault",
+            pool_key.as_ref(),
+            &[bumps.pool_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // Pay the seller out of the pool vault, Royalties go to the creators passed in order as remaining accounts
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.pool_vault.to_account_info(),
+                seller: self.seller.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            &royalties,
+            remaining_accounts,
+            signer_seeds,
+        )?;
+
+        // Transfer the NFT into the pool > The seller is the owner, so no delegate is involved
+        let transfer_program = self.token_metadata_program.to_account_info();
+        let token = &self.seller_ata.to_account_info();
+        let token_owner = &self.seller.to_account_info();
+        let destination_token = &self.pool_ata.to_account_info();
+        let destination_owner = &self.pool.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let authority = &self.seller.to_account_info();
+        let payer = &self.seller.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            &transfer_program,
+            TransferCpiAccounts {
+                token,
+                token_owner,
+                destination_token,
+                destination_owner,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                destination_token_record: self.destination_token_record.as_ref(),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke()?;
+
+        Ok(())
+    }
+}
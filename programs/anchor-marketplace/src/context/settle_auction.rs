@@ -0,0 +1,360 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    metadata::{Metadata, MetadataAccount, MasterEditionAccount,
+        mpl_token_metadata::instructions::{
+            TransferCpi, TransferCpiAccounts, TransferInstructionArgs, UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs,
+            RevokeCpi, RevokeCpiAccounts, RevokeInstructionArgs,
+        },
+    },
+    associated_token::AssociatedToken
+};
+use mpl_token_metadata::types::{RevokeArgs, TransferArgs, UnlockArgs, TokenStandard};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{creator_royalties, amount_after_fees, pay_sale_in_sol, SolPayment};
+
+// Anyone can settle an auction once it has ended > The NFT goes to the highest bidder
+// and the vault pays the Fee, Royalties and seller, or the NFT is handed back if nobody bid.
+// The seller can also cancel the auction at any time as long as nobody has bid yet.
+// Seller and winner are only checked by address, so settling can't be blocked by either of
+// them reassigning their wallet to another program.
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = auction.seller)]
+    /// CHECK: checked against the auction's seller
+    pub seller: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = nft,
+        associated_token::authority = seller,
+        associated_token::token_program = token_program,
+    )]
+    pub seller_ata: InterfaceAccount<'info, TokenAccount>,
+    // Only needed when the auction has a highest bidder
+    #[account(mut)]
+    /// CHECK: checked against the auction's highest bidder
+    pub winner: Option<UncheckedAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = nft,
+        associated_token::authority = winner,
+        associated_token::token_program = token_program,
+    )]
+    pub winner_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"auction", marketplace.key().as_ref(), nft.key().as_ref()],
+        bump,
+        has_one = seller,
+        has_one = nft,
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [b"auction_vault", auction.key().as_ref()],
+        bump,
+    )]
+    pub auction_vault: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub nft: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref()],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Account<'info, MetadataAccount>,
+    #[account(
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), nft.key().as_ref(), b"edition"],
+        seeds::program = token_metadata_program.key(),
+        bump,
+    )]
+    pub edition: Account<'info, MasterEditionAccount>,
+
+    // Only needed for programmable NFTs
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub token_record: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules: Option<AccountInfo<'info>>,
+    /// CHECK: checked by the Token Metadata program
+    pub authorization_rules_program: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    /// CHECK: checked by the Token Metadata program
+    pub destination_token_record: Option<AccountInfo<'info>>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SettleAuction<'info> {
+    pub fn settle_auction(
+        &mut self,
+        bumps: SettleAuctionBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        require!(Clock::get()?.unix_timestamp >= self.auction.end_time, MarketplaceError::AuctionNotEnded);
+
+        let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
+        let seed = &[
+            b"auction",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[bumps.auction]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // A pNFT auctioned with a Sale delegate was never locked
+        let is_programmable = self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible);
+
+        if !is_programmable {
+            self.unlock(signer_seeds)?;
+        }
+
+        match self.auction.highest_bidder {
+            None => self.revoke(is_programmable, signer_seeds),
+            Some(highest_bidder) => {
+                let (Some(winner), Some(winner_ata)) = (self.winner.as_ref(), self.winner_ata.as_ref()) else {
+                    return err!(MarketplaceError::InvalidBidder);
+                };
+                require_keys_eq!(winner.key(), highest_bidder, MarketplaceError::InvalidBidder);
+
+                self.pay_seller(bumps.auction_vault, remaining_accounts)?;
+
+                // Transfer the NFT to the winner > The auction is still the delegate, so it signs
+                let transfer_program = self.token_metadata_program.to_account_info();
+                let token = &self.seller_ata.to_account_info();
+                let token_owner = &self.seller.to_account_info();
+                let destination_token = &winner_ata.to_account_info();
+                let destination_owner = &winner.to_account_info();
+                let mint = &self.nft.to_account_info();
+                let metadata = &self.metadata.to_account_info();
+                let edition = &self.edition.to_account_info();
+                let authority = &self.auction.to_account_info();
+                let payer = &self.payer.to_account_info();
+                let system_program = &self.system_program.to_account_info();
+                let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+                let spl_token_program = &self.token_program.to_account_info();
+                let spl_ata_program = &self.associated_token_program.to_account_info();
+
+                let transfer_cpi = TransferCpi::new(
+                    &transfer_program,
+                    TransferCpiAccounts {
+                        token,
+                        token_owner,
+                        destination_token,
+                        destination_owner,
+                        mint,
+                        metadata,
+                        edition: Some(edition),
+                        token_record: self.token_record.as_ref(),
+                        destination_token_record: self.destination_token_record.as_ref(),
+                        authority,
+                        payer,
+                        system_program,
+                        sysvar_instructions,
+                        spl_token_program,
+                        spl_ata_program,
+                        authorization_rules_program: self.authorization_rules_program.as_ref(),
+                        authorization_rules: self.authorization_rules.as_ref(),
+                    },
+                    TransferInstructionArgs {
+                        transfer_args: TransferArgs::V1 {
+                            amount: 1,
+                            authorization_data: None,
+                        },
+                    }
+                );
+
+                transfer_cpi.invoke_signed(signer_seeds)?;
+
+                Ok(())
+            }
+        }
+    }
+
+    // Lets the seller take the NFT back before anyone has bid, e.g. when the reserve was set too high
+    pub fn cancel_auction(
+        &mut self,
+        bumps: SettleAuctionBumps,
+    ) -> Result<()> {
+
+        require_keys_eq!(self.payer.key(), self.auction.seller, MarketplaceError::InvalidSeller);
+        require!(self.auction.highest_bidder.is_none(), MarketplaceError::AuctionHasBids);
+
+        let marketplace_key = self.marketplace.key();
+        let nft_key = self.nft.key();
+        let seed = &[
+            b"auction",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[bumps.auction]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let is_programmable = self.metadata.token_standard == Some(TokenStandard::ProgrammableNonFungible);
+
+        if !is_programmable {
+            self.unlock(signer_seeds)?;
+        }
+
+        self.revoke(is_programmable, signer_seeds)
+    }
+
+    fn unlock(
+        &self,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        let unlock_program = &self.token_metadata_program.to_account_info();
+        let authority = &self.auction.to_account_info();
+        let token_owner = &self.seller.to_account_info();
+        let token = &self.seller_ata.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let payer = &self.payer.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        let unlock_cpi = UnlockCpi::new(
+            unlock_program,
+            UnlockCpiAccounts {
+                authority,
+                token_owner: Some(token_owner),
+                token,
+                mint,
+                metadata,
+                edition: Some(edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            UnlockInstructionArgs {
+                unlock_args: UnlockArgs::V1 {
+                    authorization_data: None,
+                },
+            }
+        );
+
+        unlock_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+
+    // Nobody bid > The seller may not be signing, so the auction revokes itself as the delegate
+    fn revoke(
+        &self,
+        is_programmable: bool,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+
+        let revoke_program = &self.token_metadata_program.to_account_info();
+        let delegate = &self.auction.to_account_info();
+        let metadata = &self.metadata.to_account_info();
+        let edition = &self.edition.to_account_info();
+        let mint = &self.nft.to_account_info();
+        let token = &self.seller_ata.to_account_info();
+        let authority = &self.auction.to_account_info();
+        let payer = &self.payer.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        let revoke_cpi = RevokeCpi::new(
+            revoke_program,
+            RevokeCpiAccounts {
+                delegate_record: None,
+                delegate,
+                metadata,
+                master_edition: Some(edition),
+                token_record: self.token_record.as_ref(),
+                mint,
+                token: Some(token),
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: self.authorization_rules_program.as_ref(),
+                authorization_rules: self.authorization_rules.as_ref(),
+            },
+            RevokeInstructionArgs {
+                revoke_args: if is_programmable { RevokeArgs::SaleV1 } else { RevokeArgs::StandardV1 },
+            },
+        );
+
+        revoke_cpi.invoke_signed(signer_seeds)?;
+
+        Ok(())
+    }
+
+    fn pay_seller(
+        &self,
+        auction_vault_bump: u8,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let auction_key = self.auction.key();
+        let seed = &[
+            b"auction_vault",
+            auction_key.as_ref(),
+            &[auction_vault_bump]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        let fee = self.marketplace.fee_amount(self.auction.highest_bid);
+        let royalties = creator_royalties(&self.metadata, self.auction.highest_bid);
+        // The seller gets whatever else the auction vault holds
+        let seller_amount = amount_after_fees(self.auction_vault.lamports(), fee, &royalties)?;
+
+        // Royalties go to the creators, passed in order as remaining accounts
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.auction_vault.to_account_info(),
+                seller: self.seller.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            &royalties,
+            remaining_accounts,
+            signer_seeds,
+        )
+    }
+}
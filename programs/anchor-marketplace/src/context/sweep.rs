@@ -0,0 +1,262 @@
+pub use anchor_lang::prelude::*;
+
+pub use solana_program::sysvar::instructions::ID as INSTRUCTIONS_ID;
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TokenInterface},
+    metadata::{Metadata, MetadataAccount,
+    mpl_token_metadata::{
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs, UnlockCpi, UnlockCpiAccounts, UnlockInstructionArgs},
+        types::TokenStandard,
+    }},
+    associated_token::{AssociatedToken, Create, create_idempotent, get_associated_token_address_with_program_id},
+};
+use mpl_token_metadata::types::{TransferArgs, UnlockArgs};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::utils::{check_metadata_accounts, creator_royalties, amount_after_fees, pay_sale_in_sol, SolPayment, release_listing_vault};
+
+// Accounts passed for each listing: listing, lister, lister_ata, buyer_ata, nft, metadata, edition.
+// For an escrowed listing the listing vault takes the place of lister_ata.
+// They are followed by the listing's creators, the same ones `buy` takes as remaining accounts.
+pub const SWEEP_LISTING_ACCOUNTS: usize = 7;
+
+#[derive(Accounts)]
+pub struct Sweep<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    #[account(address = INSTRUCTIONS_ID)]
+    /// CHECK: no need to check it out
+    pub sysvar_instruction: AccountInfo<'info>,
+    pub token_metadata_program: Program<'info, Metadata>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Sweep<'info> {
+    // Buys the listings in order while the budget lasts. A listing that was already filled, or that this
+    // sweep can't buy (expired, reserved, priced in a token, programmable, over budget), is skipped
+    // instead of failing the whole sweep.
+    pub fn sweep(
+        &mut self,
+        budget: u64,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+
+        let mut remaining_budget = budget;
+        let mut accounts = remaining_accounts;
+
+        while !accounts.is_empty() {
+            require_gte!(accounts.len(), SWEEP_LISTING_ACCOUNTS, MarketplaceError::MissingSweepAccounts);
+            let (listing_accounts, rest) = accounts.split_at(SWEEP_LISTING_ACCOUNTS);
+
+            // The metadata outlives the listing, so it tells us how many creators follow even for a filled listing
+            check_metadata_accounts(&listing_accounts[4], &listing_accounts[5], &listing_accounts[6])?;
+            let metadata = Account::<MetadataAccount>::try_from(&listing_accounts[5])?;
+            let creator_count = creator_royalties(&metadata, 0).len();
+            require_gte!(rest.len(), creator_count, MarketplaceError::InvalidCreator);
+            let (creator_accounts, rest) = rest.split_at(creator_count);
+            accounts = rest;
+
+            if let Some(price) = self.buy_listing(listing_accounts, &metadata, creator_accounts, remaining_budget)? {
+                remaining_budget -= price;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Returns the price paid, or None when the listing was skipped
+    fn buy_listing(
+        &self,
+        listing_accounts: &'info [AccountInfo<'info>],
+        metadata: &Account<'info, MetadataAccount>,
+        creator_accounts: &[AccountInfo<'info>],
+        budget: u64,
+    ) -> Result<Option<u64>> {
+
+        let [listing_info, lister, lister_ata, buyer_ata, nft, metadata_info, edition] = listing_accounts else {
+            return err!(MarketplaceError::MissingSweepAccounts);
+        };
+
+        // Already filled or delisted
+        if listing_info.data_is_empty() || listing_info.owner != &crate::ID {
+            return Ok(None);
+        }
+
+        let marketplace_key = self.marketplace.key();
+        let (listing_key, listing_bump) = Pubkey::find_program_address(
+            &[b"listing", marketplace_key.as_ref(), nft.key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(listing_info.key(), listing_key, ErrorCode::ConstraintSeeds);
+        let listing = Account::<Listing>::try_from(listing_info)?;
+
+        // A different lister means the NFT was sold and listed again since the sweep was built
+        let now = Clock::get()?.unix_timestamp;
+        if listing.lister != lister.key()
+            || listing.is_expired(now)
+            || !listing.can_buy(&self.buyer.key())
+            || listing.payment_mint.is_some()
+            || metadata.token_standard != Some(TokenStandard::NonFungible)
+        {
+            return Ok(None);
+        }
+
+        let price = listing.current_price(now);
+        if price > budget {
+            return Ok(None);
+        }
+
+        // Same split as `buy`
+        let fee = self.marketplace.fee_amount(price);
+        let royalties = creator_royalties(metadata, price);
+        let seller_amount = amount_after_fees(price, fee, &royalties)?;
+
+        pay_sale_in_sol(
+            &SolPayment {
+                from: self.buyer.to_account_info(),
+                seller: lister.to_account_info(),
+                fee_vault: self.fee_vault.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            seller_amount,
+            fee,
+            &royalties,
+            creator_accounts,
+            &[],
+        )?;
+
+        // The buyer's token account may not exist yet
+        let create_program = self.associated_token_program.to_account_info();
+        let create_accounts = Create {
+            payer: self.buyer.to_account_info(),
+            associated_token: buyer_ata.to_account_info(),
+            authority: self.buyer.to_account_info(),
+            mint: nft.to_account_info(),
+            system_program: self.system_program.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        let create_cpi = CpiContext::new(create_program, create_accounts);
+
+        create_idempotent(create_cpi)?;
+
+        let nft_key = nft.key();
+        let seed = &[
+            b"listing",
+            marketplace_key.as_ref(),
+            nft_key.as_ref(),
+            &[listing_bump]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        // An escrowed NFT is sent straight out of the listing vault
+        if listing.escrowed {
+            let listing_vault = InterfaceAccount::<TokenAccount>::try_from(lister_ata)?;
+            require_keys_eq!(
+                listing_vault.key(),
+                get_associated_token_address_with_program_id(&listing_key, &nft_key, &self.token_program.key()),
+                MarketplaceError::MissingListingVault
+            );
+            release_listing_vault(
+                &listing_vault,
+                &InterfaceAccount::<Mint>::try_from(nft)?,
+                buyer_ata,
+                &listing.to_account_info(),
+                lister,
+                &self.token_program.to_account_info(),
+                signer_seeds,
+            )?;
+            listing.close(lister.to_account_info())?;
+
+            return Ok(Some(price));
+        }
+
+        // Unlock the NFT before transfering it
+        let token_metadata_program = &self.token_metadata_program.to_account_info();
+        let authority = &listing.to_account_info();
+        let payer = &self.buyer.to_account_info();
+        let system_program = &self.system_program.to_account_info();
+        let sysvar_instructions = &self.sysvar_instruction.to_account_info();
+        let spl_token_program = &self.token_program.to_account_info();
+
+        let unlock_cpi = UnlockCpi::new(
+            token_metadata_program,
+            UnlockCpiAccounts {
+                authority,
+                token_owner: Some(lister),
+                token: lister_ata,
+                mint: nft,
+                metadata: metadata_info,
+                edition: Some(edition),
+                token_record: None,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program: Some(spl_token_program),
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            UnlockInstructionArgs {
+                unlock_args: UnlockArgs::V1 {
+                    authorization_data: None,
+                },
+            }
+        );
+
+        unlock_cpi.invoke_signed(signer_seeds)?;
+
+        // Transfer the NFT > Then we close the listing so we don't need to revoke the delegation
+        let spl_ata_program = &self.associated_token_program.to_account_info();
+
+        let transfer_cpi = TransferCpi::new(
+            token_metadata_program,
+            TransferCpiAccounts {
+                token: lister_ata,
+                token_owner: lister,
+                destination_token: buyer_ata,
+                destination_owner: payer,
+                mint: nft,
+                metadata: metadata_info,
+                edition: Some(edition),
+                token_record: None,
+                destination_token_record: None,
+                authority,
+                payer,
+                system_program,
+                sysvar_instructions,
+                spl_token_program,
+                spl_ata_program,
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke_signed(signer_seeds)?;
+
+        listing.close(lister.to_account_info())?;
+
+        Ok(Some(price))
+    }
+}
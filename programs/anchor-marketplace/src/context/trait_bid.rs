@@ -0,0 +1,122 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+use anchor_spl::{
+    token_interface::{Mint, TokenAccount, TransferChecked, transfer_checked, TokenInterface},
+    associated_token::AssociatedToken,
+};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+#[instruction(root: [u8; 32])]
+pub struct TraitBid<'info> {
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        init,
+        payer = bidder,
+        seeds = [b"trait_bid", marketplace.key().as_ref(), root.as_ref(), bidder.key().as_ref()],
+        bump,
+        space = TraitBidState::INIT_SPACE,
+    )]
+    pub trait_bid: Account<'info, TraitBidState>,
+
+    #[account(
+        mut,
+        seeds = [b"bid_vault", trait_bid.key().as_ref()],
+        bump,
+    )]
+    pub bid_vault: SystemAccount<'info>,
+
+    // Only needed when the bid is escrowed in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bidder,
+        associated_token::token_program = token_program,
+    )]
+    pub bidder_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        associated_token::mint = payment_mint,
+        associated_token::authority = bid_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub bid_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TraitBid<'info> {
+    pub fn trait_bid(
+        &mut self,
+        root: [u8; 32],
+        amount: u64,
+        payment_mint: Option<Pubkey>,
+    ) -> Result<()> {
+
+        require!(amount > 0, MarketplaceError::InvalidAmount);
+        if let Some(payment_mint) = payment_mint {
+            require!(self.marketplace.payment_mints.contains(&payment_mint), MarketplaceError::InvalidPaymentMint);
+        }
+
+        self.trait_bid.set_inner(
+            TraitBidState {
+                bidder: self.bidder.key(),
+                root,
+                price: amount,
+                payment_mint,
+            }
+        );
+
+        match payment_mint {
+            None => {
+                let transfer_program = self.system_program.to_account_info();
+                let transfer_account = Transfer {
+                    from: self.bidder.to_account_info(),
+                    to: self.bid_vault.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(transfer_program, transfer_account);
+
+                transfer(cpi_ctx, amount)?;
+            }
+            Some(payment_mint) => {
+                let (Some(mint), Some(bidder_payment_ata), Some(bid_vault_payment_ata)) = (
+                    self.payment_mint.as_ref(),
+                    self.bidder_payment_ata.as_ref(),
+                    self.bid_vault_payment_ata.as_ref(),
+                ) else {
+                    return err!(MarketplaceError::MissingPaymentAccounts);
+                };
+                require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+                let transfer_program = self.token_program.to_account_info();
+                let transfer_accounts = TransferChecked {
+                    from: bidder_payment_ata.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: bid_vault_payment_ata.to_account_info(),
+                    authority: self.bidder.to_account_info(),
+                };
+                let cpi_ctx = CpiContext::new(transfer_program, transfer_accounts);
+
+                transfer_checked(cpi_ctx, amount, mint.decimals)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+pub use anchor_lang::prelude::*;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+use crate::events::ListingPriceUpdated;
+
+#[derive(Accounts)]
+pub struct UpdateListing<'info> {
+    pub lister: Signer<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"listing", marketplace.key().as_ref(), listing.nft.as_ref()],
+        bump,
+        has_one = lister,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+impl<'info> UpdateListing<'info> {
+    pub fn update_listing(
+        &mut self,
+        price: u64,
+    ) -> Result<()> {
+
+        // A Dutch listing's `price` is only where its curve started, so it can be fixed at that price too
+        require!(price > 0 && (price != self.listing.price || self.listing.dutch_auction.is_some()), MarketplaceError::InvalidAmount);
+
+        emit!(ListingPriceUpdated {
+            listing: self.listing.key(),
+            nft: self.listing.nft,
+            old_price: self.listing.price,
+            new_price: price,
+        });
+
+        // A fixed price ends a Dutch auction
+        self.listing.price = price;
+        self.listing.dutch_auction = None;
+
+        Ok(())
+    }
+}
\ No newline at end of file
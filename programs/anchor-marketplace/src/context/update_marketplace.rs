@@ -0,0 +1,53 @@
+pub use anchor_lang::prelude::*;
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+pub struct UpdateMarketplace<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+        has_one = admin,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+}
+
+impl<'info> UpdateMarketplace<'info> {
+    pub fn update_marketplace(
+        &mut self,
+        fee: u16,
+    ) -> Result<()> {
+
+        require!(fee <= Marketplace::MAX_FEE, MarketplaceError::FeeTooHigh);
+
+        self.marketplace.fee = fee;
+
+        Ok(())
+    }
+
+    pub fn update_payment_mints(
+        &mut self,
+        payment_mints: Vec<Pubkey>,
+    ) -> Result<()> {
+
+        require_gte!(Marketplace::MAX_PAYMENT_MINTS, payment_mints.len(), MarketplaceError::TooManyPaymentMints);
+
+        self.marketplace.payment_mints = payment_mints;
+
+        Ok(())
+    }
+
+    // Only affects new listings, open ones are settled the way they were listed
+    pub fn update_listing_mode(
+        &mut self,
+        escrow_listings: bool,
+    ) -> Result<()> {
+
+        self.marketplace.escrow_listings = escrow_listings;
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+pub use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer}
+};
+
+use anchor_spl::token_interface::{Mint, TokenAccount, TransferChecked, transfer_checked, TokenInterface};
+
+pub use crate::state::*;
+pub use crate::errors::*;
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub treasury: SystemAccount<'info>,
+
+    #[account(
+        seeds = [b"marketplace", marketplace.name.as_bytes(), marketplace.creator.key().as_ref()],
+        bump,
+        has_one = admin,
+    )]
+    pub marketplace: Account<'info, Marketplace>,
+    #[account(
+        mut,
+        seeds = [b"fee_vault", marketplace.key().as_ref()],
+        bump,
+    )]
+    pub fee_vault: SystemAccount<'info>,
+
+    // Only needed to withdraw fees collected in an SPL token
+    pub payment_mint: Option<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = payment_mint,
+        associated_token::authority = fee_vault,
+        associated_token::token_program = token_program,
+    )]
+    pub fee_vault_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = payment_mint,
+        token::authority = treasury,
+        token::token_program = token_program,
+    )]
+    pub treasury_payment_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawFees<'info> {
+    pub fn withdraw_fees(
+        &mut self,
+        amount: Option<u64>,
+        bumps: WithdrawFeesBumps,
+    ) -> Result<()> {
+
+        let marketplace_key = self.marketplace.key();
+        let seed = &[
+            b"fee_vault",
+            marketplace_key.as_ref(),
+            &[bumps.fee_vault]
+        ];
+        let signer_seeds = &[&seed[..]];
+
+        if let (Some(mint), Some(fee_vault_payment_ata), Some(treasury_payment_ata)) = (
+            self.payment_mint.as_ref(),
+            self.fee_vault_payment_ata.as_ref(),
+            self.treasury_payment_ata.as_ref(),
+        ) {
+            let available = fee_vault_payment_ata.amount;
+            let amount = amount.unwrap_or(available);
+
+            require!(amount > 0 && amount <= available, MarketplaceError::InvalidAmount);
+
+            let transfer_program = self.token_program.to_account_info();
+            let transfer_accounts = TransferChecked {
+                from: fee_vault_payment_ata.to_account_info(),
+                mint: mint.to_account_info(),
+                to: treasury_payment_ata.to_account_info(),
+                authority: self.fee_vault.to_account_info(),
+            };
+            let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+            return transfer_checked(transfer_cpi, amount, mint.decimals);
+        }
+
+        // A payment mint without both token accounts must not fall back to withdrawing lamports
+        require!(
+            self.payment_mint.is_none() && self.fee_vault_payment_ata.is_none() && self.treasury_payment_ata.is_none(),
+            MarketplaceError::MissingPaymentAccounts
+        );
+
+        // The vault always keeps its rent-exempt minimum so it can keep collecting fees
+        let rent_exempt = Rent::get()?.minimum_balance(0);
+        let available = self.fee_vault.lamports().saturating_sub(rent_exempt);
+        let amount = amount.unwrap_or(available);
+
+        require!(amount > 0 && amount <= available, MarketplaceError::InvalidAmount);
+
+        let transfer_program = self.system_program.to_account_info();
+        let transfer_accounts = Transfer {
+            from: self.fee_vault.to_account_info(),
+            to: self.treasury.to_account_info(),
+        };
+        let transfer_cpi = CpiContext::new_with_signer(transfer_program, transfer_accounts, signer_seeds);
+
+        transfer(transfer_cpi, amount)?;
+
+        Ok(())
+    }
+}
\ No newline at end of file
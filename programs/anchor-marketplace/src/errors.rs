@@ -7,17 +7,77 @@ pub enum MarketplaceError {
     #[msg("Not the right Collection")]
     InvalidCollection,
     #[msg("Choose Another Amount")]
-    InvalidAmount
-}
-
-#[error_code]
-pub enum InstrospectionError {
-    #[msg("Invalid Program")]
-    InvalidTokenProgram,
-    #[msg("Invalid Instruction")]
-    InvalidIx,
-    #[msg("Invalid Amount")]
     InvalidAmount,
-    #[msg("Invalid Creator")]
-    InvalidCreator
-}
\ No newline at end of file
+    #[msg("Fee is above the maximum allowed")]
+    FeeTooHigh,
+    #[msg("Not the pending Admin")]
+    InvalidAdmin,
+    #[msg("Creator accounts don't match the metadata")]
+    InvalidCreator,
+    #[msg("Bid vault doesn't hold the bid price")]
+    BidVaultMismatch,
+    #[msg("Payment mint is not accepted")]
+    InvalidPaymentMint,
+    #[msg("Too many payment mints")]
+    TooManyPaymentMints,
+    #[msg("Payment accounts are missing")]
+    MissingPaymentAccounts,
+    #[msg("Listing has expired")]
+    ListingExpired,
+    #[msg("Listing has not expired")]
+    ListingNotExpired,
+    #[msg("Token Record is required for programmable NFTs")]
+    MissingTokenRecord,
+    #[msg("Not a Core asset of this Collection")]
+    InvalidAsset,
+    #[msg("Mint is not part of the trait bid")]
+    InvalidProof,
+    #[msg("Auction start and end times are invalid")]
+    InvalidAuctionTimes,
+    #[msg("Auction is not live")]
+    AuctionNotLive,
+    #[msg("Auction has not ended")]
+    AuctionNotEnded,
+    #[msg("Bid is below the reserve or the minimum increment")]
+    BidTooLow,
+    #[msg("Not the highest bidder of the auction")]
+    InvalidBidder,
+    #[msg("Listing is reserved for another buyer")]
+    ReservedListing,
+    #[msg("Price is above the buyer's maximum")]
+    PriceAboveMax,
+    #[msg("Listing is not from the expected lister")]
+    UnexpectedLister,
+    #[msg("Sweep accounts are missing")]
+    MissingSweepAccounts,
+    #[msg("Bulk listing accounts are missing")]
+    MissingBulkAccounts,
+    #[msg("Listing vault is required for escrowed listings")]
+    MissingListingVault,
+    #[msg("Escrowed listings are not supported here")]
+    EscrowNotSupported,
+    #[msg("Swap NFT accounts are missing")]
+    MissingSwapAccounts,
+    #[msg("Too many NFTs in the swap")]
+    TooManySwapNfts,
+    #[msg("Not the NFT the swap asks for")]
+    InvalidSwapNft,
+    #[msg("Spread is too high or the pool doesn't trade both sides")]
+    InvalidPoolSpread,
+    #[msg("Pool doesn't trade in this direction")]
+    InvalidPoolSide,
+    #[msg("Pool can't afford the NFT")]
+    InsufficientPoolFunds,
+    #[msg("Price is below the seller's minimum")]
+    PriceBelowMin,
+    #[msg("Not the seller of the auction")]
+    InvalidSeller,
+    #[msg("Auction already has bids")]
+    AuctionHasBids,
+    #[msg("Pool price would drop to zero")]
+    InvalidPoolPrice,
+    #[msg("Marketplace already uses the current layout")]
+    MarketplaceAlreadyMigrated,
+    #[msg("Token-2022 mints with a transfer hook are not supported")]
+    TransferHookNotSupported,
+}
@@ -0,0 +1,9 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct ListingPriceUpdated {
+    pub listing: Pubkey,
+    pub nft: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+}
\ No newline at end of file
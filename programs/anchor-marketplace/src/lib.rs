@@ -3,6 +3,8 @@ use anchor_lang::prelude::*;
 mod state;
 mod errors;
 mod context;
+mod utils;
+mod events;
 
 use context::*;
 
@@ -16,24 +18,36 @@ pub mod anchor_marketplace {
         ctx.accounts.initialize(name, fee)
     }
 
-    pub fn list(ctx: Context<List>, price: u64) -> Result<()> {
-        ctx.accounts.list(price, ctx.bumps)
+    pub fn list(ctx: Context<List>, price: u64, payment_mint: Option<Pubkey>, expires_at: Option<i64>, reserved_buyer: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.list(price, payment_mint, expires_at, reserved_buyer, ctx.bumps)
+    }
+
+    pub fn list_dutch(ctx: Context<List>, dutch_auction: DutchAuction, payment_mint: Option<Pubkey>, reserved_buyer: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.list_dutch(dutch_auction, payment_mint, reserved_buyer, ctx.bumps)
+    }
+
+    pub fn update_listing(ctx: Context<UpdateListing>, price: u64) -> Result<()> {
+        ctx.accounts.update_listing(price)
     }
 
     pub fn delist(ctx: Context<Delist>) -> Result<()> {
         ctx.accounts.delist(ctx.bumps)
     }
 
-    pub fn buy(ctx: Context<Buy>) -> Result<()> {
-        ctx.accounts.buy(ctx.bumps)
+    pub fn close_expired_listing(ctx: Context<CloseExpiredListing>) -> Result<()> {
+        ctx.accounts.close_expired_listing(ctx.bumps)
+    }
+
+    pub fn buy<'info>(ctx: Context<'_, '_, '_, 'info, Buy<'info>>, max_price: u64, expected_lister: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.buy(max_price, expected_lister, ctx.bumps, ctx.remaining_accounts)
     }
 
     pub fn bid(ctx: Context<Bid>, amount: u64) -> Result<()> {
         ctx.accounts.bid(amount)
     }
 
-    pub fn accept_bid(ctx: Context<AcceptBid>) -> Result<()> {
-        ctx.accounts.accept_bid(ctx.bumps)
+    pub fn accept_bid<'info>(ctx: Context<'_, '_, '_, 'info, AcceptBid<'info>>, min_price: u64) -> Result<()> {
+        ctx.accounts.accept_bid(min_price, ctx.bumps, ctx.remaining_accounts)
     }
 
     pub fn cancel_bid(ctx: Context<CancelBid>) -> Result<()> {
@@ -43,4 +57,160 @@ pub mod anchor_marketplace {
     pub fn modify_bid(ctx: Context<ModifyBid>, amount: u64) -> Result<()> {
         ctx.accounts.modify_bid(amount, ctx.bumps)
     }
+
+    pub fn migrate_listing(ctx: Context<MigrateListing>) -> Result<()> {
+        ctx.accounts.migrate_listing(ctx.bumps)
+    }
+
+    pub fn migrate_marketplace(ctx: Context<MigrateMarketplace>, name: String) -> Result<()> {
+        ctx.accounts.migrate_marketplace(name)
+    }
+
+    pub fn update_marketplace(ctx: Context<UpdateMarketplace>, fee: u16) -> Result<()> {
+        ctx.accounts.update_marketplace(fee)
+    }
+
+    pub fn update_payment_mints(ctx: Context<UpdateMarketplace>, payment_mints: Vec<Pubkey>) -> Result<()> {
+        ctx.accounts.update_payment_mints(payment_mints)
+    }
+
+    pub fn update_listing_mode(ctx: Context<UpdateMarketplace>, escrow_listings: bool) -> Result<()> {
+        ctx.accounts.update_listing_mode(escrow_listings)
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.propose_admin(new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        ctx.accounts.accept_admin()
+    }
+
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>, amount: Option<u64>) -> Result<()> {
+        ctx.accounts.withdraw_fees(amount, ctx.bumps)
+    }
+
+    pub fn list_core(ctx: Context<ListCore>, price: u64, payment_mint: Option<Pubkey>, expires_at: Option<i64>, reserved_buyer: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.list_core(price, payment_mint, expires_at, reserved_buyer)
+    }
+
+    pub fn delist_core(ctx: Context<DelistCore>) -> Result<()> {
+        ctx.accounts.delist_core(ctx.bumps)
+    }
+
+    pub fn buy_core<'info>(ctx: Context<'_, '_, '_, 'info, BuyCore<'info>>, max_price: u64, expected_lister: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.buy_core(max_price, expected_lister, ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn accept_bid_core<'info>(ctx: Context<'_, '_, '_, 'info, AcceptBidCore<'info>>, min_price: u64) -> Result<()> {
+        ctx.accounts.accept_bid_core(min_price, ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn close_expired_listing_core(ctx: Context<CloseExpiredListingCore>) -> Result<()> {
+        ctx.accounts.close_expired_listing_core(ctx.bumps)
+    }
+
+    pub fn collection_bid(ctx: Context<CollectionBid>, price: u64, quantity: u64, payment_mint: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.collection_bid(price, quantity, payment_mint)
+    }
+
+    pub fn accept_collection_bid<'info>(ctx: Context<'_, '_, '_, 'info, AcceptCollectionBid<'info>>) -> Result<()> {
+        ctx.accounts.accept_collection_bid(ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_collection_bid(ctx: Context<CancelCollectionBid>) -> Result<()> {
+        ctx.accounts.cancel_collection_bid(ctx.bumps)
+    }
+
+    pub fn offer(ctx: Context<Offer>, amount: u64, payment_mint: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.offer(amount, payment_mint)
+    }
+
+    pub fn accept_offer<'info>(ctx: Context<'_, '_, '_, 'info, AcceptOffer<'info>>) -> Result<()> {
+        ctx.accounts.accept_offer(ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        ctx.accounts.cancel_offer(ctx.bumps)
+    }
+
+    pub fn trait_bid(ctx: Context<TraitBid>, root: [u8; 32], amount: u64, payment_mint: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.trait_bid(root, amount, payment_mint)
+    }
+
+    pub fn accept_trait_bid<'info>(ctx: Context<'_, '_, '_, 'info, AcceptTraitBid<'info>>, proof: Vec<[u8; 32]>) -> Result<()> {
+        ctx.accounts.accept_trait_bid(proof, ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_trait_bid(ctx: Context<CancelTraitBid>) -> Result<()> {
+        ctx.accounts.cancel_trait_bid(ctx.bumps)
+    }
+
+    pub fn create_auction(ctx: Context<CreateAuction>, reserve_price: u64, min_increment: u64, start_time: i64, end_time: i64, extension_window: i64) -> Result<()> {
+        ctx.accounts.create_auction(reserve_price, min_increment, start_time, end_time, extension_window, ctx.bumps)
+    }
+
+    pub fn place_auction_bid(ctx: Context<PlaceAuctionBid>, amount: u64) -> Result<()> {
+        ctx.accounts.place_auction_bid(amount, ctx.bumps)
+    }
+
+    pub fn settle_auction<'info>(ctx: Context<'_, '_, '_, 'info, SettleAuction<'info>>) -> Result<()> {
+        ctx.accounts.settle_auction(ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_auction(ctx: Context<SettleAuction>) -> Result<()> {
+        ctx.accounts.cancel_auction(ctx.bumps)
+    }
+
+    pub fn sweep<'info>(ctx: Context<'_, '_, 'info, 'info, Sweep<'info>>, budget: u64) -> Result<()> {
+        ctx.accounts.sweep(budget, ctx.remaining_accounts)
+    }
+
+    pub fn list_many<'info>(ctx: Context<'_, '_, 'info, 'info, ListMany<'info>>, prices: Vec<u64>, payment_mint: Option<Pubkey>, expires_at: Option<i64>) -> Result<()> {
+        ctx.accounts.list_many(prices, payment_mint, expires_at, ctx.remaining_accounts)
+    }
+
+    pub fn delist_many<'info>(ctx: Context<'_, '_, 'info, 'info, DelistMany<'info>>) -> Result<()> {
+        ctx.accounts.delist_many(ctx.remaining_accounts)
+    }
+
+    pub fn create_swap<'info>(ctx: Context<'_, '_, 'info, 'info, CreateSwap<'info>>, seed: u64, wanted: SwapTarget, amount: u64) -> Result<()> {
+        ctx.accounts.create_swap(seed, wanted, amount, ctx.remaining_accounts)
+    }
+
+    pub fn accept_swap<'info>(ctx: Context<'_, '_, '_, 'info, AcceptSwap<'info>>) -> Result<()> {
+        ctx.accounts.accept_swap(ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_swap<'info>(ctx: Context<'_, '_, '_, 'info, CancelSwap<'info>>) -> Result<()> {
+        ctx.accounts.cancel_swap(ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn create_pool(ctx: Context<CreatePool>, seed: u64, collection: Pubkey, kind: PoolKind, curve: BondingCurve, spot_price: u64, spread: u16) -> Result<()> {
+        ctx.accounts.create_pool(seed, collection, kind, curve, spot_price, spread)
+    }
+
+    pub fn deposit_pool_sol(ctx: Context<PoolSol>, amount: u64) -> Result<()> {
+        ctx.accounts.deposit_pool_sol(amount)
+    }
+
+    pub fn withdraw_pool_sol(ctx: Context<PoolSol>, amount: Option<u64>) -> Result<()> {
+        ctx.accounts.withdraw_pool_sol(amount, ctx.bumps)
+    }
+
+    pub fn deposit_pool_nft(ctx: Context<PoolNft>) -> Result<()> {
+        ctx.accounts.deposit_pool_nft()
+    }
+
+    pub fn withdraw_pool_nft(ctx: Context<PoolNft>) -> Result<()> {
+        ctx.accounts.withdraw_pool_nft(ctx.bumps)
+    }
+
+    pub fn buy_from_pool<'info>(ctx: Context<'_, '_, '_, 'info, BuyFromPool<'info>>, max_price: u64) -> Result<()> {
+        ctx.accounts.buy_from_pool(max_price, ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn sell_to_pool<'info>(ctx: Context<'_, '_, '_, 'info, SellToPool<'info>>, min_price: u64) -> Result<()> {
+        ctx.accounts.sell_to_pool(min_price, ctx.bumps, ctx.remaining_accounts)
+    }
 }
@@ -3,12 +3,27 @@ use anchor_lang::prelude::*;
 #[account]
 pub struct Marketplace {
     pub admin: Pubkey,
+    pub pending_admin: Option<Pubkey>,
+    pub creator: Pubkey,
     pub fee: u16,
+    pub payment_mints: Vec<Pubkey>,
     pub name: String,
+    // New Token Metadata listings move the NFT into a listing vault instead of delegating and locking it.
+    // pNFTs keep their Sale delegate either way.
+    pub escrow_listings: bool,
 }
 
 impl Space for Marketplace {
-    const INIT_SPACE: usize = 8 + 32 + 2 + 4;
+    const INIT_SPACE: usize = 8 + 32 + (1 + 32) + 32 + 2 + (4 + 32 * Marketplace::MAX_PAYMENT_MINTS) + 4 + 1;
+}
+
+impl Marketplace {
+    pub const MAX_FEE: u16 = 1000;
+    pub const MAX_PAYMENT_MINTS: usize = 5;
+
+    pub fn fee_amount(&self, price: u64) -> u64 {
+        (price.checked_mul(self.fee as u64).unwrap()).checked_div(10000).unwrap()
+    }
 }
 
 #[account]
@@ -17,18 +32,367 @@ pub struct Listing {
     pub nft: Pubkey,
     pub collection: Pubkey,
     pub price: u64,
+    pub payment_mint: Option<Pubkey>,
+    pub expires_at: Option<i64>,
+    pub dutch_auction: Option<DutchAuction>,
+    // Set for an OTC deal > Only this wallet can buy the listing
+    pub reserved_buyer: Option<Pubkey>,
+    // The NFT sits in the listing vault rather than locked in the lister's wallet
+    pub escrowed: bool,
 }
 
 impl Space for Listing {
-    const INIT_SPACE: usize = 8 + 32 + 32 + 32 + 8;
+    const INIT_SPACE: usize = 8 + 32 + 32 + 32 + 8 + (1 + 32) + (1 + 8) + (1 + DutchAuction::SIZE) + (1 + 32) + 1;
+}
+
+impl Listing {
+    pub fn is_expired(&self, now: i64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if now >= expires_at)
+    }
+
+    pub fn can_buy(&self, buyer: &Pubkey) -> bool {
+        !matches!(self.reserved_buyer, Some(reserved_buyer) if reserved_buyer != *buyer)
+    }
+
+    // What a buyer pays right now > A Dutch listing ignores `price` and follows its curve
+    pub fn current_price(&self, now: i64) -> u64 {
+        match self.dutch_auction {
+            None => self.price,
+            Some(dutch_auction) => dutch_auction.price_at(now),
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PriceDecay {
+    Linear,
+    // The part of the price above `end_price` halves every `half_life` seconds
+    Exponential { half_life: i64 },
+}
+
+// A listing whose price falls from `start_price` to `end_price` between `start_time` and `end_time`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct DutchAuction {
+    pub start_price: u64,
+    pub end_price: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub decay: PriceDecay,
+}
+
+impl DutchAuction {
+    pub const SIZE: usize = 8 + 8 + 8 + 8 + (1 + 8);
+
+    pub fn price_at(&self, now: i64) -> u64 {
+        if now <= self.start_time {
+            return self.start_price;
+        }
+        if now >= self.end_time {
+            return self.end_price;
+        }
+
+        let drop = (self.start_price - self.end_price) as u128;
+        // Widened before subtracting so no pair of timestamps can overflow
+        let elapsed = (now as i128 - self.start_time as i128) as u128;
+        let duration = (self.end_time as i128 - self.start_time as i128) as u128;
+
+        let premium = match self.decay {
+            PriceDecay::Linear => drop * (duration - elapsed) / duration,
+            PriceDecay::Exponential { half_life } => {
+                let half_life = half_life as u128;
+                let halvings = elapsed / half_life;
+                let step = if halvings >= 64 { 0 } else { drop >> halvings };
+                // Interpolate between two halvings so the price doesn't move in steps
+                step - step * (elapsed % half_life) / (2 * half_life)
+            }
+        };
+
+        self.end_price + premium as u64
+    }
+}
+
+// A timed English auction > The NFT stays delegated and locked in the seller's wallet
+// while the highest bid sits in the auction vault.
+#[account]
+pub struct Auction {
+    pub seller: Pubkey,
+    pub nft: Pubkey,
+    pub collection: Pubkey,
+    pub reserve_price: u64,
+    pub min_increment: u64,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub extension_window: i64,
+    pub highest_bidder: Option<Pubkey>,
+    pub highest_bid: u64,
+}
+
+impl Space for Auction {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + (1 + 32) + 8;
+}
+
+impl Auction {
+    pub const MAX_EXTENSION_WINDOW: i64 = 24 * 60 * 60;
+
+    pub fn is_live(&self, now: i64) -> bool {
+        now >= self.start_time && now < self.end_time
+    }
+
+    // The first bid has to meet the reserve, every later one has to beat the highest bid by the increment
+    pub fn min_bid(&self) -> Option<u64> {
+        match self.highest_bidder {
+            None => Some(self.reserve_price.max(1)),
+            Some(_) => self.highest_bid.checked_add(self.min_increment.max(1)),
+        }
+    }
+}
+
+// Layout of the marketplaces created before admin handover, payment mints and listing modes
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyMarketplace {
+    pub admin: Pubkey,
+    pub fee: u16,
+    pub name: String,
+}
+
+impl LegacyMarketplace {
+    pub const SIZE: usize = 8 + 32 + 2 + 4;
+}
+
+// Layout of the listings created before they were keyed by mint
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyListing {
+    pub lister: Pubkey,
+    pub nft: Pubkey,
+    pub collection: Pubkey,
+    pub price: u64,
 }
 
 #[account]
 pub struct BidState {
     pub bidder: Pubkey,
     pub price: u64,
+    pub payment_mint: Option<Pubkey>,
 }
 
 impl Space for BidState {
-    const INIT_SPACE: usize = 8 + 32 + 8;
+    const INIT_SPACE: usize = 8 + 32 + 8 + (1 + 32);
+}
+
+// A standing offer on any verified member of `collection`, filled up to `quantity` times
+#[account]
+pub struct CollectionBidState {
+    pub bidder: Pubkey,
+    pub collection: Pubkey,
+    pub price: u64,
+    pub quantity: u64,
+    pub payment_mint: Option<Pubkey>,
+}
+
+impl Space for CollectionBidState {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 8 + (1 + 32);
+}
+
+// An offer on a single NFT, keyed by its mint, that doesn't need the NFT to be listed
+#[account]
+pub struct OfferState {
+    pub bidder: Pubkey,
+    pub nft: Pubkey,
+    pub price: u64,
+    pub payment_mint: Option<Pubkey>,
+}
+
+impl Space for OfferState {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + (1 + 32);
+}
+
+// An offer on any NFT whose mint is a leaf of `root`, e.g. every mint with a given trait
+#[account]
+pub struct TraitBidState {
+    pub bidder: Pubkey,
+    pub root: [u8; 32],
+    pub price: u64,
+    pub payment_mint: Option<Pubkey>,
+}
+
+impl Space for TraitBidState {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + (1 + 32);
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SwapTarget {
+    Nft(Pubkey),
+    // Any verified member of the collection
+    Collection(Pubkey),
+}
+
+// An offer to trade the escrowed `offered_nfts`, plus `amount` lamports, for the NFT `wanted`
+#[account]
+pub struct SwapState {
+    pub maker: Pubkey,
+    pub seed: u64,
+    pub offered_nfts: Vec<Pubkey>,
+    pub amount: u64,
+    pub wanted: SwapTarget,
+}
+
+impl Space for SwapState {
+    const INIT_SPACE: usize = 8 + 32 + 8 + (4 + 32 * SwapState::MAX_OFFERED_NFTS) + 8 + (1 + 32);
+}
+
+impl SwapState {
+    pub const MAX_OFFERED_NFTS: usize = 5;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PoolKind {
+    // Only buys NFTs with its lamports
+    Token,
+    // Only sells its NFTs
+    Nft,
+    // Both, earning the spread on each side
+    Trade,
+}
+
+// How the spot price moves by one step after each trade
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BondingCurve {
+    Linear { delta: u64 },
+    Exponential { delta_bps: u16 },
+}
+
+impl BondingCurve {
+    pub const SIZE: usize = 1 + 8;
+
+    pub fn step_up(&self, price: u64) -> Option<u64> {
+        match *self {
+            BondingCurve::Linear { delta } => price.checked_add(delta),
+            BondingCurve::Exponential { delta_bps } => {
+                let price = price as u128 * (10000 + delta_bps as u128) / 10000;
+                u64::try_from(price).ok()
+            }
+        }
+    }
+
+    pub fn step_down(&self, price: u64) -> u64 {
+        match *self {
+            BondingCurve::Linear { delta } => price.saturating_sub(delta),
+            BondingCurve::Exponential { delta_bps } => (price as u128 * 10000 / (10000 + delta_bps as u128)) as u64,
+        }
+    }
+}
+
+// A bonding-curve pool for one verified collection. Its lamports sit in the pool vault and its NFTs
+// in the pool's associated token accounts. The pool sells at `spot_price` and buys one step below it.
+#[account]
+pub struct Pool {
+    pub owner: Pubkey,
+    pub seed: u64,
+    pub collection: Pubkey,
+    pub kind: PoolKind,
+    pub curve: BondingCurve,
+    pub spot_price: u64,
+    // Trade pools only > Added to the price they sell at and taken off the price they buy at
+    pub spread: u16,
+    pub nft_count: u32,
+}
+
+impl Space for Pool {
+    const INIT_SPACE: usize = 8 + 32 + 8 + 32 + 1 + BondingCurve::SIZE + 8 + 2 + 4;
+}
+
+impl Pool {
+    pub const MAX_SPREAD: u16 = 5000;
+
+    fn spread_amount(&self, price: u64) -> u64 {
+        (price as u128 * self.spread as u128 / 10000) as u64
+    }
+
+    // What a buyer pays for the next NFT out of the pool
+    pub fn sell_price(&self) -> Option<u64> {
+        self.spot_price.checked_add(self.spread_amount(self.spot_price))
+    }
+
+    // What a seller gets for the next NFT into the pool
+    pub fn buy_price(&self) -> u64 {
+        let price = self.curve.step_down(self.spot_price);
+        price - self.spread_amount(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dutch_auction(decay: PriceDecay) -> DutchAuction {
+        DutchAuction {
+            start_price: 1_000,
+            end_price: 200,
+            start_time: 100,
+            end_time: 200,
+            decay,
+        }
+    }
+
+    #[test]
+    fn linear_price_falls_evenly() {
+        let auction = dutch_auction(PriceDecay::Linear);
+
+        assert_eq!(auction.price_at(125), 800);
+        assert_eq!(auction.price_at(150), 600);
+        assert_eq!(auction.price_at(175), 400);
+    }
+
+    #[test]
+    fn exponential_price_halves_every_half_life() {
+        let auction = dutch_auction(PriceDecay::Exponential { half_life: 10 });
+
+        assert_eq!(auction.price_at(105), 800);
+        assert_eq!(auction.price_at(110), 600);
+        assert_eq!(auction.price_at(115), 500);
+        assert_eq!(auction.price_at(120), 400);
+    }
+
+    #[test]
+    fn price_never_drops_below_the_floor() {
+        for decay in [PriceDecay::Linear, PriceDecay::Exponential { half_life: 3 }] {
+            let auction = DutchAuction { end_time: 10_000, ..dutch_auction(decay) };
+
+            let mut previous = auction.start_price;
+            for now in auction.start_time..=auction.end_time {
+                let price = auction.price_at(now);
+                assert!(price >= auction.end_price);
+                assert!(price <= previous);
+                previous = price;
+            }
+        }
+    }
+
+    #[test]
+    fn price_handles_extreme_times() {
+        for decay in [PriceDecay::Linear, PriceDecay::Exponential { half_life: i64::MAX }] {
+            let auction = DutchAuction { start_time: i64::MIN, end_time: i64::MAX, ..dutch_auction(decay) };
+
+            assert_eq!(auction.price_at(i64::MIN), 1_000);
+            assert_eq!(auction.price_at(i64::MAX), 200);
+            let price = auction.price_at(0);
+            assert!((200..=1_000).contains(&price));
+        }
+    }
+
+    #[test]
+    fn price_is_the_start_price_before_start() {
+        let auction = dutch_auction(PriceDecay::Linear);
+
+        assert_eq!(auction.price_at(0), 1_000);
+        assert_eq!(auction.price_at(100), 1_000);
+    }
+
+    #[test]
+    fn price_is_the_end_price_after_end() {
+        let auction = dutch_auction(PriceDecay::Exponential { half_life: 10 });
+
+        assert_eq!(auction.price_at(200), 200);
+        assert_eq!(auction.price_at(i64::MAX), 200);
+    }
 }
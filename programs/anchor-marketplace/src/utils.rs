@@ -0,0 +1,536 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{Transfer, transfer, CreateAccount, create_account, Allocate, allocate, Assign, assign},
+    solana_program::keccak::hashv,
+};
+use anchor_spl::{
+    metadata::{MetadataAccount, mpl_token_metadata::{
+        accounts::{Metadata, MasterEdition},
+        instructions::{TransferCpi, TransferCpiAccounts, TransferInstructionArgs},
+    }},
+    token_interface::{Mint, TokenAccount, TransferChecked, transfer_checked, CloseAccount, close_account},
+    associated_token::get_associated_token_address_with_program_id,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{StateWithExtensions, transfer_hook},
+        state::Mint as MintState,
+    },
+};
+use mpl_core::{
+    accounts::{BaseAssetV1, BaseCollectionV1},
+    fetch_plugin,
+    types::{PluginType, Royalties},
+};
+
+use mpl_token_metadata::types::TransferArgs;
+
+use crate::errors::*;
+use crate::state::SwapState;
+use crate::context::create_swap::SWAP_NFT_ACCOUNTS;
+
+// Royalties owed to each creator with a share, in the order of `metadata.creators`.
+// Both sale paths (Buy and AcceptBid) take them out of the sale price.
+pub fn creator_royalties(metadata: &MetadataAccount, price: u64) -> Vec<(Pubkey, u64)> {
+    let creators = match metadata.creators.as_ref() {
+        Some(creators) if metadata.seller_fee_basis_points != 0 => creators,
+        _ => return vec![],
+    };
+
+    let amount_to_split = price
+        .checked_mul(metadata.seller_fee_basis_points as u64)
+        .unwrap()
+        .checked_div(10000)
+        .unwrap();
+
+    creators
+        .iter()
+        .filter(|creator| creator.share > 0)
+        .map(|creator| {
+            let creator_amount = amount_to_split
+                .checked_mul(creator.share as u64)
+                .unwrap()
+                .checked_div(100)
+                .unwrap();
+            (creator.address, creator_amount)
+        })
+        .collect()
+}
+
+// Royalties for a Core asset, read from the asset's royalties plugin or, failing that,
+// from its collection's. Same split as `creator_royalties`, in the order of the plugin's creators.
+pub fn core_royalties(asset: &AccountInfo, collection: &AccountInfo, price: u64) -> Vec<(Pubkey, u64)> {
+    let royalties = match fetch_plugin::<BaseAssetV1, Royalties>(asset, PluginType::Royalties) {
+        Ok((_, royalties, _)) => royalties,
+        Err(_) => match fetch_plugin::<BaseCollectionV1, Royalties>(collection, PluginType::Royalties) {
+            Ok((_, royalties, _)) => royalties,
+            Err(_) => return vec![],
+        },
+    };
+
+    let amount_to_split = price
+        .checked_mul(royalties.basis_points as u64)
+        .unwrap()
+        .checked_div(10000)
+        .unwrap();
+
+    royalties.creators
+        .iter()
+        .filter(|creator| creator.percentage > 0)
+        .map(|creator| {
+            let creator_amount = amount_to_split
+                .checked_mul(creator.percentage as u64)
+                .unwrap()
+                .checked_div(100)
+                .unwrap();
+            (creator.address, creator_amount)
+        })
+        .collect()
+}
+
+// Pays each creator from `from`. The creator accounts must be passed in the same order
+// as `metadata.creators`, one per creator with a share, so no payment can count twice.
+pub fn pay_royalties<'info>(
+    royalties: &[(Pubkey, u64)],
+    from: &AccountInfo<'info>,
+    creator_accounts: &[AccountInfo<'info>],
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    require_gte!(creator_accounts.len(), royalties.len(), MarketplaceError::InvalidCreator);
+
+    for ((creator, creator_amount), creator_account) in royalties.iter().zip(creator_accounts.iter()) {
+        require_keys_eq!(creator_account.key(), *creator, MarketplaceError::InvalidCreator);
+
+        let transfer_accounts = Transfer {
+            from: from.clone(),
+            to: creator_account.clone(),
+        };
+        let transfer_cpi = CpiContext::new_with_signer(system_program.clone(), transfer_accounts, signer_seeds);
+
+        transfer(transfer_cpi, *creator_amount)?;
+    }
+
+    Ok(())
+}
+
+// Same as `pay_royalties` for listings settled in an SPL token: the creator accounts
+// are the creators' associated token accounts for the payment mint under `token_program`.
+pub fn pay_token_royalties<'info>(
+    royalties: &[(Pubkey, u64)],
+    payment_mint: &InterfaceAccount<'info, Mint>,
+    from: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    creator_accounts: &[AccountInfo<'info>],
+    token_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    require_gte!(creator_accounts.len(), royalties.len(), MarketplaceError::InvalidCreator);
+
+    for ((creator, creator_amount), creator_account) in royalties.iter().zip(creator_accounts.iter()) {
+        require_keys_eq!(creator_account.key(), get_associated_token_address_with_program_id(creator, &payment_mint.key(), token_program.key), MarketplaceError::InvalidCreator);
+
+        let transfer_accounts = TransferChecked {
+            from: from.clone(),
+            mint: payment_mint.to_account_info(),
+            to: creator_account.clone(),
+            authority: authority.clone(),
+        };
+        let transfer_cpi = CpiContext::new_with_signer(token_program.clone(), transfer_accounts, signer_seeds);
+
+        transfer_checked(transfer_cpi, *creator_amount, payment_mint.decimals)?;
+    }
+
+    Ok(())
+}
+
+// Who pays a sale in lamports and who gets paid. `from` is the buyer, or a bid vault signing with its seeds.
+pub struct SolPayment<'info> {
+    pub from: AccountInfo<'info>,
+    pub seller: AccountInfo<'info>,
+    pub fee_vault: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+// Pays `seller_amount` to the seller, the Fee to the fee vault and Royalties to the creators,
+// passed in order as `creator_accounts`. Shared by every sale paid in lamports.
+pub fn pay_sale_in_sol<'info>(
+    payment: &SolPayment<'info>,
+    seller_amount: u64,
+    fee: u64,
+    royalties: &[(Pubkey, u64)],
+    creator_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    // Pay the seller
+    let transfer_accounts = Transfer {
+        from: payment.from.clone(),
+        to: payment.seller.clone(),
+    };
+    let transfer_cpi = CpiContext::new_with_signer(payment.system_program.clone(), transfer_accounts, signer_seeds);
+
+    transfer(transfer_cpi, seller_amount)?;
+
+    // Pay the Fee
+    let transfer_accounts = Transfer {
+        from: payment.from.clone(),
+        to: payment.fee_vault.clone(),
+    };
+    let transfer_cpi = CpiContext::new_with_signer(payment.system_program.clone(), transfer_accounts, signer_seeds);
+
+    transfer(transfer_cpi, fee)?;
+
+    // Pay Royalties to the creators
+    pay_royalties(
+        royalties,
+        &payment.from,
+        creator_accounts,
+        &payment.system_program,
+        signer_seeds,
+    )
+}
+
+// Same as `SolPayment` for a sale paid in an SPL token: `from`, `seller` and `fee_vault` are token accounts
+// for `mint`, and `authority` owns `from`.
+pub struct TokenPayment<'a, 'info> {
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub from: AccountInfo<'info>,
+    pub authority: AccountInfo<'info>,
+    pub seller: AccountInfo<'info>,
+    pub fee_vault: AccountInfo<'info>,
+    pub token_program: AccountInfo<'info>,
+}
+
+// Same split as `pay_sale_in_sol`, the creator accounts being the creators' token accounts for the mint
+pub fn pay_sale_in_token<'info>(
+    payment: &TokenPayment<'_, 'info>,
+    seller_amount: u64,
+    fee: u64,
+    royalties: &[(Pubkey, u64)],
+    creator_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    // Pay the seller
+    let transfer_accounts = TransferChecked {
+        from: payment.from.clone(),
+        mint: payment.mint.to_account_info(),
+        to: payment.seller.clone(),
+        authority: payment.authority.clone(),
+    };
+    let transfer_cpi = CpiContext::new_with_signer(payment.token_program.clone(), transfer_accounts, signer_seeds);
+
+    transfer_checked(transfer_cpi, seller_amount, payment.mint.decimals)?;
+
+    // Pay the Fee
+    let transfer_accounts = TransferChecked {
+        from: payment.from.clone(),
+        mint: payment.mint.to_account_info(),
+        to: payment.fee_vault.clone(),
+        authority: payment.authority.clone(),
+    };
+    let transfer_cpi = CpiContext::new_with_signer(payment.token_program.clone(), transfer_accounts, signer_seeds);
+
+    transfer_checked(transfer_cpi, fee, payment.mint.decimals)?;
+
+    // Pay Royalties to the creators' token accounts
+    pay_token_royalties(
+        royalties,
+        payment.mint,
+        &payment.from,
+        &payment.authority,
+        creator_accounts,
+        &payment.token_program,
+        signer_seeds,
+    )
+}
+
+// What's left of a price once the Fee and Royalties are taken out
+pub fn amount_after_fees(price: u64, fee: u64, royalties: &[(Pubkey, u64)]) -> Result<u64> {
+    price
+        .checked_sub(fee)
+        .and_then(|amount| amount.checked_sub(royalties.iter().map(|(_, amount)| amount).sum()))
+        .ok_or(error!(MarketplaceError::InvalidAmount))
+}
+
+// Closes the token account of an emptied bid vault and gives its rent back to the bidder
+pub fn close_bid_vault_ata<'info>(
+    bid_vault_payment_ata: &AccountInfo<'info>,
+    bid_vault: &AccountInfo<'info>,
+    bidder: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    let close_accounts = CloseAccount {
+        account: bid_vault_payment_ata.clone(),
+        destination: bidder.clone(),
+        authority: bid_vault.clone(),
+    };
+    let close_cpi = CpiContext::new_with_signer(token_program.clone(), close_accounts, signer_seeds);
+
+    close_account(close_cpi)
+}
+
+// The accounts a cancelled bid, offer or collection bid is refunded with. The payment ones are only
+// needed when the bid is escrowed in an SPL token.
+pub struct BidRefund<'a, 'info> {
+    pub bid_vault: AccountInfo<'info>,
+    pub bidder: AccountInfo<'info>,
+    pub payment_mint: Option<&'a InterfaceAccount<'info, Mint>>,
+    pub bidder_payment_ata: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub bid_vault_payment_ata: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+}
+
+// Empties the bid vault back to the bidder, closing the vault's token account for a bid in `payment_mint`
+pub fn refund_bid_vault<'info>(
+    refund: &BidRefund<'_, 'info>,
+    payment_mint: Option<Pubkey>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    let Some(payment_mint) = payment_mint else {
+        let transfer_accounts = Transfer {
+            from: refund.bid_vault.clone(),
+            to: refund.bidder.clone(),
+        };
+        let transfer_cpi = CpiContext::new_with_signer(refund.system_program.clone(), transfer_accounts, signer_seeds);
+
+        return transfer(transfer_cpi, refund.bid_vault.lamports());
+    };
+
+    let (Some(mint), Some(bidder_payment_ata), Some(bid_vault_payment_ata)) = (
+        refund.payment_mint,
+        refund.bidder_payment_ata,
+        refund.bid_vault_payment_ata,
+    ) else {
+        return err!(MarketplaceError::MissingPaymentAccounts);
+    };
+    require_keys_eq!(mint.key(), payment_mint, MarketplaceError::InvalidPaymentMint);
+
+    let transfer_accounts = TransferChecked {
+        from: bid_vault_payment_ata.to_account_info(),
+        mint: mint.to_account_info(),
+        to: bidder_payment_ata.to_account_info(),
+        authority: refund.bid_vault.clone(),
+    };
+    let transfer_cpi = CpiContext::new_with_signer(refund.token_program.clone(), transfer_accounts, signer_seeds);
+
+    transfer_checked(transfer_cpi, bid_vault_payment_ata.amount, mint.decimals)?;
+
+    close_bid_vault_ata(
+        &bid_vault_payment_ata.to_account_info(),
+        &refund.bid_vault,
+        &refund.bidder,
+        &refund.token_program,
+        signer_seeds,
+    )
+}
+
+// Creates a program-owned PDA the way Anchor's `init` does. Someone can send lamports to the address
+// beforehand, and `create_account` refuses an account that has any, so in that case only the missing
+// rent is topped up before the account is allocated and assigned. Fails if the account is already in use.
+pub fn create_pda_account<'info>(
+    payer: &AccountInfo<'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    system_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    let rent = Rent::get()?.minimum_balance(space);
+    let current_lamports = account.lamports();
+
+    if current_lamports == 0 {
+        let create_accounts = CreateAccount {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        let create_cpi = CpiContext::new_with_signer(system_program.clone(), create_accounts, signer_seeds);
+
+        return create_account(create_cpi, rent, space as u64, &crate::ID);
+    }
+
+    let missing_lamports = rent.saturating_sub(current_lamports);
+    if missing_lamports > 0 {
+        let transfer_accounts = Transfer {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        let transfer_cpi = CpiContext::new(system_program.clone(), transfer_accounts);
+
+        transfer(transfer_cpi, missing_lamports)?;
+    }
+
+    let allocate_accounts = Allocate {
+        account_to_allocate: account.clone(),
+    };
+    let allocate_cpi = CpiContext::new_with_signer(system_program.clone(), allocate_accounts, signer_seeds);
+
+    allocate(allocate_cpi, space as u64)?;
+
+    let assign_accounts = Assign {
+        account_to_assign: account.clone(),
+    };
+    let assign_cpi = CpiContext::new_with_signer(system_program.clone(), assign_accounts, signer_seeds);
+
+    assign(assign_cpi, &crate::ID)
+}
+
+// Checks that `metadata` and `edition` are the Token Metadata accounts of `nft`. Contexts get this
+// from seeds constraints, NFTs passed as remaining accounts have to go through here.
+pub fn check_metadata_accounts(nft: &AccountInfo, metadata: &AccountInfo, edition: &AccountInfo) -> Result<()> {
+    require_keys_eq!(metadata.key(), Metadata::find_pda(nft.key).0, ErrorCode::ConstraintSeeds);
+    require_keys_eq!(edition.key(), MasterEdition::find_pda(nft.key).0, ErrorCode::ConstraintSeeds);
+
+    Ok(())
+}
+
+// Token-2022 NFTs are supported as far as Token Metadata supports them: the metadata lives in the
+// Token Metadata PDA, not behind a metadata pointer, and the mint can't have a transfer hook since
+// Token Metadata transfers don't pass the hook's extra accounts. Checked wherever an NFT comes in.
+pub fn check_nft_mint(nft: &AccountInfo) -> Result<()> {
+    if nft.owner != &spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = nft.try_borrow_data()?;
+    let mint = StateWithExtensions::<MintState>::unpack(&data)?;
+    require!(transfer_hook::get_program_id(&mint).is_none(), MarketplaceError::TransferHookNotSupported);
+
+    Ok(())
+}
+
+// Moves an escrowed NFT out of the listing vault to `to`, then closes the vault
+// and gives its rent back to the lister who paid for it on list.
+pub fn release_listing_vault<'info>(
+    listing_vault: &InterfaceAccount<'info, TokenAccount>,
+    nft: &InterfaceAccount<'info, Mint>,
+    to: &AccountInfo<'info>,
+    listing: &AccountInfo<'info>,
+    lister: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    let transfer_accounts = TransferChecked {
+        from: listing_vault.to_account_info(),
+        mint: nft.to_account_info(),
+        to: to.clone(),
+        authority: listing.clone(),
+    };
+    let transfer_cpi = CpiContext::new_with_signer(token_program.clone(), transfer_accounts, signer_seeds);
+
+    transfer_checked(transfer_cpi, 1, nft.decimals)?;
+
+    let close_accounts = CloseAccount {
+        account: listing_vault.to_account_info(),
+        destination: lister.clone(),
+        authority: listing.clone(),
+    };
+    let close_cpi = CpiContext::new_with_signer(token_program.clone(), close_accounts, signer_seeds);
+
+    close_account(close_cpi)
+}
+
+// The programs a Token Metadata transfer goes through
+pub struct TransferPrograms<'info> {
+    pub token_metadata_program: AccountInfo<'info>,
+    pub system_program: AccountInfo<'info>,
+    pub sysvar_instructions: AccountInfo<'info>,
+    pub spl_token_program: AccountInfo<'info>,
+    pub spl_ata_program: AccountInfo<'info>,
+}
+
+// Sends every NFT escrowed by `swap` to `destination_owner`, then closes the escrow accounts and gives
+// their rent back to the maker. The NFT accounts come in the order of `swap.offered_nfts`, with the
+// swap's token account as the token and `destination_owner`'s as the destination.
+pub fn release_swap_nfts<'info>(
+    swap: &Account<'info, SwapState>,
+    maker: &AccountInfo<'info>,
+    destination_owner: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    nft_accounts: &[AccountInfo<'info>],
+    programs: &TransferPrograms<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+
+    let nfts = nft_accounts.chunks_exact(SWAP_NFT_ACCOUNTS);
+    require!(nfts.len() == swap.offered_nfts.len() && nfts.remainder().is_empty(), MarketplaceError::MissingSwapAccounts);
+
+    let swap_info = swap.to_account_info();
+
+    for (accounts, offered_nft) in nfts.zip(swap.offered_nfts.iter()) {
+        let [token, destination_token, nft, metadata, edition] = accounts else {
+            return err!(MarketplaceError::MissingSwapAccounts);
+        };
+        require_keys_eq!(nft.key(), *offered_nft, MarketplaceError::MissingSwapAccounts);
+        require_keys_eq!(
+            token.key(),
+            get_associated_token_address_with_program_id(&swap.key(), offered_nft, programs.spl_token_program.key),
+            MarketplaceError::MissingSwapAccounts
+        );
+
+        let transfer_cpi = TransferCpi::new(
+            &programs.token_metadata_program,
+            TransferCpiAccounts {
+                token,
+                token_owner: &swap_info,
+                destination_token,
+                destination_owner,
+                mint: nft,
+                metadata,
+                edition: Some(edition),
+                token_record: None,
+                destination_token_record: None,
+                authority: &swap_info,
+                payer,
+                system_program: &programs.system_program,
+                sysvar_instructions: &programs.sysvar_instructions,
+                spl_token_program: &programs.spl_token_program,
+                spl_ata_program: &programs.spl_ata_program,
+                authorization_rules_program: None,
+                authorization_rules: None,
+            },
+            TransferInstructionArgs {
+                transfer_args: TransferArgs::V1 {
+                    amount: 1,
+                    authorization_data: None,
+                },
+            }
+        );
+
+        transfer_cpi.invoke_signed(signer_seeds)?;
+
+        let close_accounts = CloseAccount {
+            account: token.clone(),
+            destination: maker.clone(),
+            authority: swap_info.clone(),
+        };
+        let close_cpi = CpiContext::new_with_signer(programs.spl_token_program.clone(), close_accounts, signer_seeds);
+
+        close_account(close_cpi)?;
+    }
+
+    Ok(())
+}
+
+// Checks that `mint` is one of the leaves of `root`. Leaves are `keccak(mint)` and each
+// level hashes the sorted pair, so a proof doesn't have to say which side a node is on.
+pub fn verify_mint_proof(mint: &Pubkey, proof: &[[u8; 32]], root: &[u8; 32]) -> bool {
+    let leaf = hashv(&[mint.as_ref()]).to_bytes();
+
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        if node <= *sibling {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        }
+    });
+
+    computed == *root
+}
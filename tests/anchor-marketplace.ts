@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program, BN, AnchorError } from "@coral-xyz/anchor";
 import { AnchorMarketplace } from "../target/types/anchor_marketplace";
+import { assert } from "chai";
 import {
   LAMPORTS_PER_SOL,
   PublicKey,
@@ -11,20 +12,43 @@ import {
 import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
   getAssociatedTokenAddressSync,
   getOrCreateAssociatedTokenAccount,
-  createTransferInstruction
+  createTransferInstruction,
+  createMint,
+  mintTo,
+  getAccount,
+  ExtensionType,
+  getMintLen,
+  createInitializeTransferHookInstruction,
+  createInitializeMint2Instruction,
 } from "@solana/spl-token";
 import {
   createNft,
+  createProgrammableNft,
+  createV1,
+  mintV1,
+  delegateStandardV1,
+  TokenStandard,
   mplTokenMetadata,
   verifyCollection, 
+  verifyCollectionV1,
   deserializeMetadata,
   fetchMetadataFromSeeds,
   findMetadataPda,
 } from "@metaplex-foundation/mpl-token-metadata";
 import { MPL_TOKEN_METADATA_PROGRAM_ID } from '@metaplex-foundation/mpl-token-metadata';
+import {
+  MPL_CORE_PROGRAM_ID,
+  create as createCoreAsset,
+  createCollection as createCoreCollection,
+  fetchAsset,
+  fetchCollection,
+  ruleSet,
+} from "@metaplex-foundation/mpl-core";
 import { base58 } from "@metaplex-foundation/umi/serializers";
+import { keccak_256 } from "@noble/hashes/sha3";
 import { createUmi } from "@metaplex-foundation/umi-bundle-defaults"
 import { 
   createSignerFromKeypair, 
@@ -59,6 +83,58 @@ describe("anchor-marketplace", () => {
     return signature;
   };
 
+  const mintVerifiedNft = async (owner: anchor.web3.Keypair): Promise<{ mint: PublicKey, metadata: PublicKey, edition: PublicKey }> => {
+    const umi = createUmi(connection.rpcEndpoint);
+    const ownerSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(owner.secretKey));
+    const adminSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(admin.secretKey));
+    const mintSigner = generateSigner(umi);
+    umi.use(signerIdentity(ownerSigner));
+    umi.use(mplTokenMetadata())
+
+    await createNft(
+      umi,
+      {
+        mint: mintSigner,
+        authority: ownerSigner,
+        updateAuthority: ownerSigner.publicKey,
+        name: "NFT Example",
+        symbol: "EXM",
+        uri: "",
+        sellerFeeBasisPoints: percentAmount(1),
+        creators: [
+            {address: ownerSigner.publicKey, verified: true, share: 50 },
+            {address: adminSigner.publicKey, verified: false, share: 50}
+        ],
+        collection: {verified: false, key: publicKey(collectionMint)},
+        uses: null,
+        isMutable: true,
+        collectionDetails: null,
+      }
+    ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+    const mint = new PublicKey(mintSigner.publicKey);
+    const metadata_seeds = [
+      Buffer.from('metadata'),
+      new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(),
+      mint.toBuffer(),
+    ];
+    const metadata = PublicKey.findProgramAddressSync(metadata_seeds, new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID))[0];
+    const edition = PublicKey.findProgramAddressSync([...metadata_seeds, Buffer.from("edition")], new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID))[0];
+
+    await verifyCollection(
+      umi,
+      {
+        metadata: publicKey(metadata),
+        collectionAuthority: adminSigner,
+        collectionMint: publicKey(collectionMint),
+        collection: publicKey(collectionMetadata),
+        collectionMasterEditionAccount: publicKey(collectionMasterEdition),
+      }
+    ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+    return { mint, metadata, edition };
+  };
+
   let marketplacePda: anchor.web3.PublicKey;
   let feeVault: anchor.web3.PublicKey;
   let listingPda: anchor.web3.PublicKey;
@@ -88,7 +164,7 @@ describe("anchor-marketplace", () => {
   it("Creates a new marketplace", async () => {
 
     const name = "Test Marketplace #2";
-    const fee = 0.05;
+    const fee = 500;
 
     marketplacePda = await PublicKey.findProgramAddressSync(([Buffer.from("marketplace"), Buffer.from(name), admin.publicKey.toBuffer()]), program.programId)[0];
     feeVault = await PublicKey.findProgramAddressSync(([Buffer.from("fee_vault"), marketplacePda.toBuffer()]), program.programId)[0];
@@ -105,6 +181,78 @@ describe("anchor-marketplace", () => {
       .signers([admin]).rpc({skipPreflight: true}).then(confirm).then(log);
   });
 
+  it("Updates the marketplace fee", async () => {
+
+    await program.methods
+      .updateMarketplace(250)
+      .accounts({
+        admin: admin.publicKey,
+        marketplace: marketplacePda,
+      })
+      .signers([admin]).rpc().then(confirm).then(log);
+
+    const marketplace = await program.account.marketplace.fetch(marketplacePda);
+    assert.equal(marketplace.fee, 250);
+  });
+
+  it("Rejects a fee above the maximum", async () => {
+
+    try {
+      await program.methods
+        .updateMarketplace(10001)
+        .accounts({
+          admin: admin.publicKey,
+          marketplace: marketplacePda,
+        })
+        .signers([admin]).rpc();
+      assert.fail("fee above the maximum should be rejected");
+    } catch (e) {
+      assert.equal((e as AnchorError).error.errorCode.code, "FeeTooHigh");
+    }
+  });
+
+  it("Hands over the admin in two steps", async () => {
+
+    const newAdmin = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .proposeAdmin(newAdmin.publicKey)
+      .accounts({
+        admin: admin.publicKey,
+        marketplace: marketplacePda,
+      })
+      .signers([admin]).rpc().then(confirm).then(log);
+
+    await program.methods
+      .acceptAdmin()
+      .accounts({
+        pendingAdmin: newAdmin.publicKey,
+        marketplace: marketplacePda,
+      })
+      .signers([newAdmin]).rpc().then(confirm).then(log);
+
+    let marketplace = await program.account.marketplace.fetch(marketplacePda);
+    assert.ok(marketplace.admin.equals(newAdmin.publicKey));
+    assert.isNull(marketplace.pendingAdmin);
+
+    // Hand it back so the rest of the suite keeps using the original admin
+    await program.methods
+      .proposeAdmin(admin.publicKey)
+      .accounts({
+        admin: newAdmin.publicKey,
+        marketplace: marketplacePda,
+      })
+      .signers([newAdmin]).rpc().then(confirm).then(log);
+
+    await program.methods
+      .acceptAdmin()
+      .accounts({
+        pendingAdmin: admin.publicKey,
+        marketplace: marketplacePda,
+      })
+      .signers([admin]).rpc().then(confirm).then(log);
+  });
+
   it("Mint Collection NFT", async () => {
 
     // Metaplex Setup
@@ -265,7 +413,7 @@ describe("anchor-marketplace", () => {
 
   it("Creates Listing", async () => {
 
-    listingPda = await PublicKey.findProgramAddressSync(([Buffer.from("listing"), marketplacePda.toBuffer()]), program.programId)[0];
+    listingPda = await PublicKey.findProgramAddressSync(([Buffer.from("listing"), marketplacePda.toBuffer(), nftMint.toBuffer()]), program.programId)[0];
     
     const ata = await getOrCreateAssociatedTokenAccount(connection, lister, nftMint, listingPda, true);
     listingVault = ata.address;
@@ -276,7 +424,7 @@ describe("anchor-marketplace", () => {
 
     try {
     const tx = await program.methods
-      .list(price)
+      .list(price, null, null, null)
       .accounts({
         lister: lister.publicKey,
         listerAta,
@@ -337,7 +485,7 @@ describe("anchor-marketplace", () => {
     // let metadataInfo = await fetchMetadata(umi, publicKey(nftMetadata));
     // console.log(metadataInfo);
 
-    // For Testing Purposes we know that lister gets 100% of the royalty
+    // The creators are paid by the program, in the order of the metadata creators
     let creator1 = lister.publicKey;
     let share1 = 50;
     let creator2 = admin.publicKey;
@@ -345,51 +493,2998 @@ describe("anchor-marketplace", () => {
     let seller_fee_basis_points = 100;
     let price = 1 * LAMPORTS_PER_SOL;
 
-    let tx = new Transaction();
+    const marketplace = await program.account.marketplace.fetch(marketplacePda);
+    const feeVaultBefore = await connection.getBalance(feeVault);
+    const creator2Before = await connection.getBalance(creator2);
+    const listerBefore = await connection.getBalance(lister.publicKey);
+    const listingRent = await connection.getBalance(listingPda);
 
-    const buyTx = await program.methods
-      .buy()
+    try {
+      await program.methods
+        .buy(new BN(price), lister.publicKey)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta,
+          listerAta,
+          marketplace: marketplacePda,
+          feeVault,
+          listing: listingPda,
+          nft: nftMint,
+          metadata: nftMetadata,
+          edition: nftMasterEdition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: creator1, isSigner: false, isWritable: true },
+          { pubkey: creator2, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+    } catch(e) {
+      console.log(e);
+      throw(e)
+    }
+
+    assert.equal(await connection.getBalance(creator2), creator2Before + price * seller_fee_basis_points * share2 / 1000000);
+
+    // The Fee comes out of the price, like when a bid is accepted
+    const fee = price * marketplace.fee / 10000;
+    assert.equal(await connection.getBalance(feeVault), feeVaultBefore + fee);
+
+    // The lister gets the rest, plus their own creator share and the listing rent back
+    const royalties = price * seller_fee_basis_points / 10000;
+    const sellerAmount = price - fee - royalties;
+    assert.equal(await connection.getBalance(lister.publicKey), listerBefore + sellerAmount + royalties * share1 / 100 + listingRent);
+  });
+
+  it("Withdraws fees", async () => {
+
+    const treasury = anchor.web3.Keypair.generate().publicKey;
+    const rentExempt = await connection.getMinimumBalanceForRentExemption(0);
+    const vaultBalance = await connection.getBalance(feeVault);
+
+    await program.methods
+      .withdrawFees(null)
       .accounts({
-        buyer: buyer.publicKey,
-        lister: lister.publicKey,
-        buyerAta,
-        listerAta,
+        admin: admin.publicKey,
+        treasury,
         marketplace: marketplacePda,
         feeVault,
-        listing: listingPda,
-        nft: nftMint,
-        metadata: nftMetadata,
-        edition: nftMasterEdition,
-        sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
-        tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
-        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
-      // .signers([buyer]).rpc().then(confirm).then(log);
+      .signers([admin]).rpc().then(confirm).then(log);
 
-      .instruction()
+    assert.equal(await connection.getBalance(treasury), vaultBalance - rentExempt);
+    assert.equal(await connection.getBalance(feeVault), rentExempt);
+  });
 
-      tx.instructions = [
-        buyTx,
-        SystemProgram.transfer({
-          fromPubkey: buyer.publicKey,
-          toPubkey: creator1,
-          lamports: price * seller_fee_basis_points * share1 / 1000000,
-        }),
-        SystemProgram.transfer({
-          fromPubkey: buyer.publicKey,
-          toPubkey: creator2,
-          lamports: price * seller_fee_basis_points * share2 / 1000000,
-        }),
-      ]
+  describe("Creator royalties", () => {
+    let royaltyNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let royaltyListing: PublicKey;
+
+    const price = new BN(LAMPORTS_PER_SOL);
+
+    const buyWithCreators = async (creators: PublicKey[]) => {
+      await program.methods
+        .buy(price, null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: getAssociatedTokenAddressSync(royaltyNft.mint, buyer.publicKey),
+          listerAta: getAssociatedTokenAddressSync(royaltyNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: royaltyListing,
+          nft: royaltyNft.mint,
+          metadata: royaltyNft.metadata,
+          edition: royaltyNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(creators.map((creator) => ({ pubkey: creator, isSigner: false, isWritable: true })))
+        .signers([buyer]).rpc().then(confirm).then(log);
+    };
+
+    it("Lists an NFT with two creators", async () => {
+      royaltyNft = await mintVerifiedNft(lister);
+      royaltyListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), royaltyNft.mint.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .list(price, null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(royaltyNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: royaltyListing,
+          collection: collectionMint,
+          nft: royaltyNft.mint,
+          metadata: royaltyNft.metadata,
+          edition: royaltyNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+    });
+
+    it("Rejects creators passed out of order", async () => {
+      try {
+        await buyWithCreators([admin.publicKey, lister.publicKey]);
+        assert.fail("creators out of order should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "InvalidCreator");
+      }
+    });
+
+    it("Rejects a missing creator", async () => {
       try {
-        await provider.sendAndConfirm(tx, [ buyer ]).then(confirm).then(log);
-      } catch(e) {
-        console.log(e);
-        throw(e)
+        await buyWithCreators([lister.publicKey]);
+        assert.fail("a missing creator should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "InvalidCreator");
       }
+    });
+
+    it("Pays each creator their share", async () => {
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const listerBefore = await connection.getBalance(lister.publicKey);
+      const adminBefore = await connection.getBalance(admin.publicKey);
+      const listingRent = await connection.getBalance(royaltyListing);
+
+      await buyWithCreators([lister.publicKey, admin.publicKey]);
+
+      // 1% royalties split 50/50, the lister being the first creator
+      const royalties = price.toNumber() * 100 / 10000;
+      const fee = price.toNumber() * marketplace.fee / 10000;
+      assert.equal(await connection.getBalance(admin.publicKey), adminBefore + royalties / 2);
+      assert.equal(await connection.getBalance(lister.publicKey), listerBefore + price.toNumber() - fee - royalties + royalties / 2 + listingRent);
+    });
   });
 
-});
+  describe("Bids", () => {
+    let bidNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let bidListing: PublicKey;
+    let bidPda: PublicKey;
+    let bidVault: PublicKey;
+    let bidListerAta: PublicKey;
+    let bidderAta: PublicKey;
+
+    const bidAmount = new BN(LAMPORTS_PER_SOL / 2);
+
+    const placeBid = async (amount: BN) => {
+      await program.methods
+        .bid(amount)
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          listing: bidListing,
+          bid: bidPda,
+          bidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+    };
+
+    it("Lists a second NFT", async () => {
+      bidNft = await mintVerifiedNft(lister);
+
+      bidListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), bidNft.mint.toBuffer()], program.programId)[0];
+      bidPda = PublicKey.findProgramAddressSync([Buffer.from("bid"), bidListing.toBuffer(), buyer.publicKey.toBuffer()], program.programId)[0];
+      bidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), bidPda.toBuffer()], program.programId)[0];
+      bidListerAta = getAssociatedTokenAddressSync(bidNft.mint, lister.publicKey);
+      bidderAta = getAssociatedTokenAddressSync(bidNft.mint, buyer.publicKey);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: bidListerAta,
+          marketplace: marketplacePda,
+          listing: bidListing,
+          collection: collectionMint,
+          nft: bidNft.mint,
+          metadata: bidNft.metadata,
+          edition: bidNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+    });
+
+    it("Reprices the listing in place", async () => {
+      const newPrice = new BN(2 * LAMPORTS_PER_SOL);
+
+      await program.methods
+        .updateListing(newPrice)
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: bidListing,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const listing = await program.account.listing.fetch(bidListing);
+      assert.ok(listing.price.eq(newPrice));
+    });
+
+    it("Escrows the bid in the bid vault", async () => {
+      await placeBid(bidAmount);
+
+      assert.equal(await connection.getBalance(bidVault), bidAmount.toNumber());
+      const bid = await program.account.bidState.fetch(bidPda);
+      assert.ok(bid.price.eq(bidAmount));
+    });
+
+    const modifyBid = async (amount: BN) => {
+      await program.methods
+        .modifyBid(amount)
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          listing: bidListing,
+          bid: bidPda,
+          bidVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+    };
+
+    it("Raises the bid", async () => {
+      const raised = bidAmount.muln(2);
+      await modifyBid(raised);
+
+      assert.equal(await connection.getBalance(bidVault), raised.toNumber());
+    });
+
+    it("Refunds the difference when the bid is lowered", async () => {
+      const before = await connection.getBalance(buyer.publicKey);
+      await modifyBid(bidAmount);
+
+      assert.equal(await connection.getBalance(bidVault), bidAmount.toNumber());
+      assert.isAbove(await connection.getBalance(buyer.publicKey), before + bidAmount.toNumber() - 10000);
+    });
+
+    it("Refunds the bidder on cancel", async () => {
+      const before = await connection.getBalance(buyer.publicKey);
+
+      await program.methods
+        .cancelBid()
+        .accounts({
+          bidder: buyer.publicKey,
+          listing: bidListing,
+          bid: bidPda,
+          bidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal(await connection.getBalance(bidVault), 0);
+      assert.isNull(await connection.getAccountInfo(bidPda));
+      // The bidder gets the escrow and the bid account rent back, minus the transaction fee
+      assert.isAbove(await connection.getBalance(buyer.publicKey), before + bidAmount.toNumber() - 10000);
+    });
+
+    const acceptBid = async (minPrice: BN) => {
+      await program.methods
+        .acceptBid(minPrice)
+        .accounts({
+          lister: lister.publicKey,
+          bidder: buyer.publicKey,
+          bidderAta,
+          listerAta: bidListerAta,
+          marketplace: marketplacePda,
+          feeVault,
+          listing: bidListing,
+          bid: bidPda,
+          bidVault,
+          nft: bidNft.mint,
+          metadata: bidNft.metadata,
+          edition: bidNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([lister]).rpc().then(confirm).then(log);
+    };
+
+    it("Rejects accepting a bid under the lister's minimum", async () => {
+      await placeBid(bidAmount);
+
+      try {
+        await acceptBid(bidAmount.addn(1));
+        assert.fail("bid under the minimum was accepted");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "PriceBelowMin");
+      }
+    });
+
+    it("Pays the lister, the fee vault and the creators on accept", async () => {
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const fee = bidAmount.toNumber() * marketplace.fee / 10000;
+      const royalties = bidAmount.toNumber() * 100 / 10000;
+
+      const feeVaultBefore = await connection.getBalance(feeVault);
+      const adminBefore = await connection.getBalance(admin.publicKey);
+      const listerBefore = await connection.getBalance(lister.publicKey);
+      const listingRent = await connection.getBalance(bidListing);
+      // The lister pays for the bidder's token account and the transaction
+      const ataRent = await connection.getMinimumBalanceForRentExemption(165);
+      const txFee = 5000;
+
+      await acceptBid(bidAmount);
+
+      assert.equal(await connection.getBalance(bidVault), 0);
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + fee);
+      assert.equal(await connection.getBalance(admin.publicKey), adminBefore + royalties / 2);
+      // Same split as a buy: the lister gets the rest, plus their own creator share and the listing rent back
+      const sellerAmount = bidAmount.toNumber() - fee - royalties;
+      assert.equal(await connection.getBalance(lister.publicKey), listerBefore + sellerAmount + royalties / 2 + listingRent - ataRent - txFee);
+      assert.isNull(await connection.getAccountInfo(bidListing));
+
+      const bidderToken = await connection.getTokenAccountBalance(bidderAta);
+      assert.equal(bidderToken.value.amount, "1");
+    });
+
+    it("Rejects modifying a bid once the listing is closed", async () => {
+      try {
+        await modifyBid(bidAmount.muln(2));
+        assert.fail("bid on a closed listing was modified");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "AccountNotInitialized");
+      }
+    });
+  });
+
+  describe("SPL token payments", () => {
+    let paymentMint: PublicKey;
+    let splNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let splListing: PublicKey;
+
+    const price = new BN(100_000_000); // 100 tokens with 6 decimals
+
+    it("Allows a payment mint on the marketplace", async () => {
+      paymentMint = await createMint(connection, admin, admin.publicKey, null, 6);
+      const buyerPaymentAta = await getOrCreateAssociatedTokenAccount(connection, buyer, paymentMint, buyer.publicKey);
+      await mintTo(connection, admin, paymentMint, buyerPaymentAta.address, admin, 1_000_000_000);
+
+      await program.methods
+        .updatePaymentMints([paymentMint])
+        .accounts({
+          admin: admin.publicKey,
+          marketplace: marketplacePda,
+        })
+        .signers([admin]).rpc().then(confirm).then(log);
+
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      assert.ok(marketplace.paymentMints[0].equals(paymentMint));
+    });
+
+    it("Lists an NFT priced in the payment mint", async () => {
+      splNft = await mintVerifiedNft(lister);
+      splListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), splNft.mint.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .list(price, paymentMint, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(splNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: splListing,
+          collection: collectionMint,
+          nft: splNft.mint,
+          metadata: splNft.metadata,
+          edition: splNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+    });
+
+    it("Buys with SPL tokens", async () => {
+      // The creators must already hold a token account for the payment mint
+      const listerPaymentAta = (await getOrCreateAssociatedTokenAccount(connection, lister, paymentMint, lister.publicKey)).address;
+      const adminPaymentAta = (await getOrCreateAssociatedTokenAccount(connection, admin, paymentMint, admin.publicKey)).address;
+      const buyerPaymentAta = getAssociatedTokenAddressSync(paymentMint, buyer.publicKey);
+      const feeVaultPaymentAta = getAssociatedTokenAddressSync(paymentMint, feeVault, true);
+
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const fee = price.toNumber() * marketplace.fee / 10000;
+      const royalties = price.toNumber() * 100 / 10000;
+
+      await program.methods
+        .buy(price, null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: getAssociatedTokenAddressSync(splNft.mint, buyer.publicKey),
+          listerAta: getAssociatedTokenAddressSync(splNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: splListing,
+          nft: splNft.mint,
+          metadata: splNft.metadata,
+          edition: splNft.edition,
+          paymentMint,
+          buyerPaymentAta,
+          listerPaymentAta,
+          feeVaultPaymentAta,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          paymentTokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: listerPaymentAta, isSigner: false, isWritable: true },
+          { pubkey: adminPaymentAta, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal((await getAccount(connection, feeVaultPaymentAta)).amount, BigInt(fee));
+      assert.equal((await getAccount(connection, adminPaymentAta)).amount, BigInt(royalties / 2));
+      assert.equal((await getAccount(connection, listerPaymentAta)).amount, BigInt(price.toNumber() - fee - royalties / 2));
+    });
+
+    it("Rejects a token withdrawal missing its token accounts", async () => {
+      const vaultBalance = await connection.getBalance(feeVault);
+
+      try {
+        await program.methods
+          .withdrawFees(null)
+          .accounts({
+            admin: admin.publicKey,
+            treasury: admin.publicKey,
+            marketplace: marketplacePda,
+            feeVault,
+            paymentMint,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([admin]).rpc();
+        assert.fail("a withdrawal with only the payment mint should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "MissingPaymentAccounts");
+      }
+
+      // No lamports left the vault instead
+      assert.equal(await connection.getBalance(feeVault), vaultBalance);
+    });
+  });
+
+  describe("Expiring listings", () => {
+    let expiringNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let expiringListing: PublicKey;
+    let expiringListerAta: PublicKey;
+
+    it("Lists an NFT with an expiry", async () => {
+      expiringNft = await mintVerifiedNft(lister);
+      expiringListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), expiringNft.mint.toBuffer()], program.programId)[0];
+      expiringListerAta = getAssociatedTokenAddressSync(expiringNft.mint, lister.publicKey);
+
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, new BN(now + 2), null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: expiringListerAta,
+          marketplace: marketplacePda,
+          listing: expiringListing,
+          collection: collectionMint,
+          nft: expiringNft.mint,
+          metadata: expiringNft.metadata,
+          edition: expiringNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+    });
+
+    it("Lets anyone close the listing once it has expired", async () => {
+      await new Promise((resolve) => setTimeout(resolve, 4000));
+
+      const keeper = anchor.web3.Keypair.generate();
+      await connection.requestAirdrop(keeper.publicKey, LAMPORTS_PER_SOL).then(confirm);
+
+      await program.methods
+        .closeExpiredListing()
+        .accounts({
+          payer: keeper.publicKey,
+          lister: lister.publicKey,
+          listerAta: expiringListerAta,
+          marketplace: marketplacePda,
+          listing: expiringListing,
+          nft: expiringNft.mint,
+          metadata: expiringNft.metadata,
+          edition: expiringNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([keeper]).rpc().then(confirm).then(log);
+
+      assert.isNull(await connection.getAccountInfo(expiringListing));
+      const listerToken = await getAccount(connection, expiringListerAta);
+      assert.isFalse(listerToken.isFrozen);
+      assert.isNull(listerToken.delegate);
+    });
+  });
+
+  describe("Programmable NFTs", () => {
+    let pnftMint: PublicKey;
+    let pnftMetadata: PublicKey;
+    let pnftEdition: PublicKey;
+    let pnftListing: PublicKey;
+    let pnftListerAta: PublicKey;
+    let pnftBuyerAta: PublicKey;
+
+    const tokenRecord = (mint: PublicKey, token: PublicKey): PublicKey => PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(),
+        mint.toBuffer(),
+        Buffer.from("token_record"),
+        token.toBuffer(),
+      ],
+      new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID)
+    )[0];
+
+    it("Lists a pNFT with a Sale delegate", async () => {
+      const umi = createUmi(connection.rpcEndpoint);
+      const listerSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(lister.secretKey));
+      const adminSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(admin.secretKey));
+      const mintSigner = generateSigner(umi);
+      umi.use(signerIdentity(listerSigner));
+      umi.use(mplTokenMetadata())
+
+      await createProgrammableNft(
+        umi,
+        {
+          mint: mintSigner,
+          authority: listerSigner,
+          name: "pNFT Example",
+          symbol: "EXM",
+          uri: "",
+          sellerFeeBasisPoints: percentAmount(1),
+          creators: [
+              {address: listerSigner.publicKey, verified: true, share: 50 },
+              {address: adminSigner.publicKey, verified: false, share: 50}
+          ],
+          collection: {verified: false, key: publicKey(collectionMint)},
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      pnftMint = new PublicKey(mintSigner.publicKey);
+      pnftMetadata = new PublicKey(findMetadataPda(umi, { mint: mintSigner.publicKey })[0]);
+      pnftEdition = PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(), pnftMint.toBuffer(), Buffer.from("edition")],
+        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID)
+      )[0];
+
+      umi.use(signerIdentity(adminSigner));
+      await verifyCollectionV1(
+        umi,
+        {
+          metadata: publicKey(pnftMetadata),
+          collectionMint: publicKey(collectionMint),
+          authority: adminSigner,
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      pnftListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), pnftMint.toBuffer()], program.programId)[0];
+      pnftListerAta = getAssociatedTokenAddressSync(pnftMint, lister.publicKey);
+      pnftBuyerAta = getAssociatedTokenAddressSync(pnftMint, buyer.publicKey);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: pnftListerAta,
+          marketplace: marketplacePda,
+          listing: pnftListing,
+          collection: collectionMint,
+          nft: pnftMint,
+          metadata: pnftMetadata,
+          edition: pnftEdition,
+          tokenRecord: tokenRecord(pnftMint, pnftListerAta),
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+    });
+
+    it("Buys a pNFT through the Sale delegate", async () => {
+      await program.methods
+        .buy(new BN(LAMPORTS_PER_SOL), null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: pnftBuyerAta,
+          listerAta: pnftListerAta,
+          marketplace: marketplacePda,
+          feeVault,
+          listing: pnftListing,
+          nft: pnftMint,
+          metadata: pnftMetadata,
+          edition: pnftEdition,
+          tokenRecord: tokenRecord(pnftMint, pnftListerAta),
+          destinationTokenRecord: tokenRecord(pnftMint, pnftBuyerAta),
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const buyerToken = await connection.getTokenAccountBalance(pnftBuyerAta);
+      assert.equal(buyerToken.value.amount, "1");
+    });
+  });
+
+  describe("Token-2022 NFTs", () => {
+    let t22Mint: PublicKey;
+    let t22Metadata: PublicKey;
+    let t22Edition: PublicKey;
+    let t22Listing: PublicKey;
+    let t22ListerAta: PublicKey;
+    let t22BuyerAta: PublicKey;
+
+    it("Lists an NFT minted under Token-2022", async () => {
+      const umi = createUmi(connection.rpcEndpoint);
+      const listerSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(lister.secretKey));
+      const adminSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(admin.secretKey));
+      const mintSigner = generateSigner(umi);
+      umi.use(signerIdentity(listerSigner));
+      umi.use(mplTokenMetadata())
+
+      await createNft(
+        umi,
+        {
+          mint: mintSigner,
+          authority: listerSigner,
+          name: "Token-2022 NFT Example",
+          symbol: "EXM",
+          uri: "",
+          sellerFeeBasisPoints: percentAmount(1),
+          creators: [
+              {address: listerSigner.publicKey, verified: true, share: 50 },
+              {address: adminSigner.publicKey, verified: false, share: 50}
+          ],
+          collection: {verified: false, key: publicKey(collectionMint)},
+          splTokenProgram: publicKey(TOKEN_2022_PROGRAM_ID),
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      t22Mint = new PublicKey(mintSigner.publicKey);
+      t22Metadata = new PublicKey(findMetadataPda(umi, { mint: mintSigner.publicKey })[0]);
+      t22Edition = PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(), t22Mint.toBuffer(), Buffer.from("edition")],
+        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID)
+      )[0];
+
+      umi.use(signerIdentity(adminSigner));
+      await verifyCollectionV1(
+        umi,
+        {
+          metadata: publicKey(t22Metadata),
+          collectionMint: publicKey(collectionMint),
+          authority: adminSigner,
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      t22Listing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), t22Mint.toBuffer()], program.programId)[0];
+      t22ListerAta = getAssociatedTokenAddressSync(t22Mint, lister.publicKey, false, TOKEN_2022_PROGRAM_ID);
+      t22BuyerAta = getAssociatedTokenAddressSync(t22Mint, buyer.publicKey, false, TOKEN_2022_PROGRAM_ID);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: t22ListerAta,
+          marketplace: marketplacePda,
+          listing: t22Listing,
+          collection: collectionMint,
+          nft: t22Mint,
+          metadata: t22Metadata,
+          edition: t22Edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+    });
+
+    it("Buys a Token-2022 NFT", async () => {
+      await program.methods
+        .buy(new BN(LAMPORTS_PER_SOL), null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: t22BuyerAta,
+          listerAta: t22ListerAta,
+          marketplace: marketplacePda,
+          feeVault,
+          listing: t22Listing,
+          nft: t22Mint,
+          metadata: t22Metadata,
+          edition: t22Edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const buyerToken = await connection.getTokenAccountBalance(t22BuyerAta);
+      assert.equal(buyerToken.value.amount, "1");
+    });
+
+    it("Rejects listing a Token-2022 NFT with a transfer hook", async () => {
+      // Token Metadata transfers don't pass the hook's accounts, so the hook program doesn't need to exist
+      const hookMint = anchor.web3.Keypair.generate();
+      const hookProgram = anchor.web3.Keypair.generate().publicKey;
+      const mintLen = getMintLen([ExtensionType.TransferHook]);
+
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          SystemProgram.createAccount({
+            fromPubkey: lister.publicKey,
+            newAccountPubkey: hookMint.publicKey,
+            space: mintLen,
+            lamports: await connection.getMinimumBalanceForRentExemption(mintLen),
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeTransferHookInstruction(hookMint.publicKey, lister.publicKey, hookProgram, TOKEN_2022_PROGRAM_ID),
+          createInitializeMint2Instruction(hookMint.publicKey, 0, lister.publicKey, lister.publicKey, TOKEN_2022_PROGRAM_ID),
+        ),
+        [lister, hookMint],
+      );
+
+      const umi = createUmi(connection.rpcEndpoint);
+      const listerSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(lister.secretKey));
+      umi.use(signerIdentity(listerSigner));
+      umi.use(mplTokenMetadata())
+
+      const mint = publicKey(hookMint.publicKey);
+      await createV1(
+        umi,
+        {
+          mint,
+          authority: listerSigner,
+          name: "Transfer Hook NFT Example",
+          uri: "",
+          sellerFeeBasisPoints: percentAmount(1),
+          tokenStandard: TokenStandard.NonFungible,
+          splTokenProgram: publicKey(TOKEN_2022_PROGRAM_ID),
+        }
+      ).add(mintV1(
+        umi,
+        {
+          mint,
+          authority: listerSigner,
+          amount: 1,
+          tokenOwner: listerSigner.publicKey,
+          tokenStandard: TokenStandard.NonFungible,
+          splTokenProgram: publicKey(TOKEN_2022_PROGRAM_ID),
+        }
+      )).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      const hookMetadata = new PublicKey(findMetadataPda(umi, { mint })[0]);
+      const hookEdition = PublicKey.findProgramAddressSync(
+        [Buffer.from("metadata"), new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(), hookMint.publicKey.toBuffer(), Buffer.from("edition")],
+        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID)
+      )[0];
+
+      try {
+        await program.methods
+          .list(new BN(LAMPORTS_PER_SOL), null, null, null)
+          .accounts({
+            lister: lister.publicKey,
+            listerAta: getAssociatedTokenAddressSync(hookMint.publicKey, lister.publicKey, false, TOKEN_2022_PROGRAM_ID),
+            marketplace: marketplacePda,
+            listing: PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), hookMint.publicKey.toBuffer()], program.programId)[0],
+            collection: collectionMint,
+            nft: hookMint.publicKey,
+            metadata: hookMetadata,
+            edition: hookEdition,
+            sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([lister]).rpc();
+        assert.fail("a transfer hook mint should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "TransferHookNotSupported");
+      }
+    });
+  });
+
+  describe("Core assets", () => {
+    let coreCollection: PublicKey;
+    let coreAsset: PublicKey;
+    let coreListing: PublicKey;
+
+    const umiFor = (owner: anchor.web3.Keypair) => {
+      const umi = createUmi(connection.rpcEndpoint);
+      const ownerSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(owner.secretKey));
+      umi.use(signerIdentity(ownerSigner));
+      return umi;
+    };
+
+    it("Lists a Core asset with Freeze and Transfer delegates", async () => {
+      const umi = umiFor(lister);
+      const collectionSigner = generateSigner(umi);
+      const assetSigner = generateSigner(umi);
+
+      await createCoreCollection(
+        umi,
+        {
+          collection: collectionSigner,
+          name: "Core Collection Example",
+          uri: "",
+          plugins: [
+            {
+              type: "Royalties",
+              basisPoints: 100,
+              creators: [
+                { address: publicKey(lister.publicKey), percentage: 50 },
+                { address: publicKey(admin.publicKey), percentage: 50 },
+              ],
+              ruleSet: ruleSet("None"),
+            },
+          ],
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      await createCoreAsset(
+        umi,
+        {
+          asset: assetSigner,
+          collection: await fetchCollection(umi, collectionSigner.publicKey),
+          name: "Core Asset Example",
+          uri: "",
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      coreCollection = new PublicKey(collectionSigner.publicKey);
+      coreAsset = new PublicKey(assetSigner.publicKey);
+      coreListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), coreAsset.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .listCore(new BN(LAMPORTS_PER_SOL), null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: coreListing,
+          asset: coreAsset,
+          collection: coreCollection,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const asset = await fetchAsset(umi, publicKey(coreAsset));
+      assert.ok(asset.freezeDelegate.frozen);
+      assert.equal(asset.transferDelegate.authority.address, coreListing.toBase58());
+    });
+
+    it("Thaws and clears the delegates on delist", async () => {
+      await program.methods
+        .delistCore()
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: coreListing,
+          asset: coreAsset,
+          collection: coreCollection,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const asset = await fetchAsset(umiFor(lister), publicKey(coreAsset));
+      assert.isUndefined(asset.freezeDelegate);
+      assert.isUndefined(asset.transferDelegate);
+    });
+
+    it("Buys a Core asset and pays the royalties plugin creators", async () => {
+      await program.methods
+        .listCore(new BN(LAMPORTS_PER_SOL), null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: coreListing,
+          asset: coreAsset,
+          collection: coreCollection,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const adminBefore = await connection.getBalance(admin.publicKey);
+
+      await program.methods
+        .buyCore(new BN(LAMPORTS_PER_SOL), null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          feeVault,
+          listing: coreListing,
+          asset: coreAsset,
+          collection: coreCollection,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const asset = await fetchAsset(umiFor(buyer), publicKey(coreAsset));
+      assert.equal(asset.owner, buyer.publicKey.toBase58());
+      assert.isFalse(asset.freezeDelegate.frozen);
+
+      // 1% royalties split evenly between the two creators
+      const adminAfter = await connection.getBalance(admin.publicKey);
+      assert.equal(adminAfter - adminBefore, LAMPORTS_PER_SOL / 100 / 2);
+    });
+
+    // The buyer now owns the asset and lists it in turn
+    const listCoreAs = async (owner: anchor.web3.Keypair, expiresAt: BN | null) => {
+      await program.methods
+        .listCore(new BN(LAMPORTS_PER_SOL), null, expiresAt, null)
+        .accounts({
+          lister: owner.publicKey,
+          marketplace: marketplacePda,
+          listing: coreListing,
+          asset: coreAsset,
+          collection: coreCollection,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([owner]).rpc().then(confirm).then(log);
+    };
+
+    it("Fills a bid on a Core asset", async () => {
+      await listCoreAs(buyer, null);
+
+      const bidAmount = new BN(LAMPORTS_PER_SOL / 2);
+      const bid = PublicKey.findProgramAddressSync([Buffer.from("bid"), coreListing.toBuffer(), lister.publicKey.toBuffer()], program.programId)[0];
+      const bidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), bid.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .bid(bidAmount)
+        .accounts({
+          bidder: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: coreListing,
+          bid,
+          bidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const feeVaultBefore = await connection.getBalance(feeVault);
+
+      await program.methods
+        .acceptBidCore(bidAmount)
+        .accounts({
+          lister: buyer.publicKey,
+          bidder: lister.publicKey,
+          marketplace: marketplacePda,
+          feeVault,
+          listing: coreListing,
+          bid,
+          bidVault,
+          asset: coreAsset,
+          collection: coreCollection,
+          paymentMint: null,
+          bidVaultPaymentAta: null,
+          listerPaymentAta: null,
+          feeVaultPaymentAta: null,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          paymentTokenProgram: null,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const asset = await fetchAsset(umiFor(lister), publicKey(coreAsset));
+      assert.equal(asset.owner, lister.publicKey.toBase58());
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + bidAmount.toNumber() * marketplace.fee / 10000);
+      assert.equal(await connection.getBalance(bidVault), 0);
+      assert.isNull(await connection.getAccountInfo(coreListing));
+    });
+
+    it("Lets anyone close an expired Core listing", async () => {
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+      await listCoreAs(lister, new BN(now + 2));
+      await new Promise((resolve) => setTimeout(resolve, 4000));
+
+      await program.methods
+        .closeExpiredListingCore()
+        .accounts({
+          payer: buyer.publicKey,
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: coreListing,
+          asset: coreAsset,
+          collection: coreCollection,
+          coreProgram: MPL_CORE_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const asset = await fetchAsset(umiFor(lister), publicKey(coreAsset));
+      assert.isFalse(asset.freezeDelegate.frozen);
+      assert.equal(asset.freezeDelegate.authority.type, "Owner");
+      assert.equal(asset.transferDelegate.authority.type, "Owner");
+      assert.isNull(await connection.getAccountInfo(coreListing));
+    });
+  });
+
+  describe("Collection offers", () => {
+    let offerNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let collectionBidPda: PublicKey;
+    let collectionBidVault: PublicKey;
+
+    const offerPrice = new BN(LAMPORTS_PER_SOL / 2);
+
+    it("Escrows the price of every NFT in the offer", async () => {
+      collectionBidPda = PublicKey.findProgramAddressSync(
+        [Buffer.from("collection_bid"), marketplacePda.toBuffer(), collectionMint.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      )[0];
+      collectionBidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), collectionBidPda.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .collectionBid(offerPrice, new BN(2), null)
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          collection: collectionMint,
+          collectionBid: collectionBidPda,
+          bidVault: collectionBidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal(await connection.getBalance(collectionBidVault), offerPrice.muln(2).toNumber());
+    });
+
+    it("Lets any holder of the collection fill the offer", async () => {
+      offerNft = await mintVerifiedNft(lister);
+
+      await program.methods
+        .acceptCollectionBid()
+        .accounts({
+          seller: lister.publicKey,
+          bidder: buyer.publicKey,
+          bidderAta: getAssociatedTokenAddressSync(offerNft.mint, buyer.publicKey),
+          sellerAta: getAssociatedTokenAddressSync(offerNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          collectionBid: collectionBidPda,
+          bidVault: collectionBidVault,
+          nft: offerNft.mint,
+          metadata: offerNft.metadata,
+          edition: offerNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(offerNft.mint, buyer.publicKey));
+      assert.equal(buyerToken.value.amount, "1");
+      const collectionBid = await program.account.collectionBidState.fetch(collectionBidPda);
+      assert.ok(collectionBid.quantity.eqn(1));
+      assert.equal(await connection.getBalance(collectionBidVault), offerPrice.toNumber());
+    });
+
+    it("Refunds what is left of the offer on cancel", async () => {
+      await program.methods
+        .cancelCollectionBid()
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          collectionBid: collectionBidPda,
+          bidVault: collectionBidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal(await connection.getBalance(collectionBidVault), 0);
+      assert.isNull(await connection.getAccountInfo(collectionBidPda));
+    });
+  });
+
+  describe("Offers on unlisted NFTs", () => {
+    let offerNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let offerPda: PublicKey;
+    let offerVault: PublicKey;
 
+    const offerAmount = new BN(LAMPORTS_PER_SOL / 2);
+
+    const makeOffer = async () => {
+      await program.methods
+        .offer(offerAmount, null)
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          nft: offerNft.mint,
+          offer: offerPda,
+          bidVault: offerVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+    };
+
+    it("Escrows an offer on an NFT that isn't listed", async () => {
+      offerNft = await mintVerifiedNft(lister);
+      offerPda = PublicKey.findProgramAddressSync(
+        [Buffer.from("offer"), marketplacePda.toBuffer(), offerNft.mint.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      )[0];
+      offerVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), offerPda.toBuffer()], program.programId)[0];
+
+      await makeOffer();
+
+      assert.equal(await connection.getBalance(offerVault), offerAmount.toNumber());
+      const offer = await program.account.offerState.fetch(offerPda);
+      assert.ok(offer.nft.equals(offerNft.mint));
+    });
+
+    it("Refunds the bidder on cancel", async () => {
+      await program.methods
+        .cancelOffer()
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          offer: offerPda,
+          bidVault: offerVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal(await connection.getBalance(offerVault), 0);
+      assert.isNull(await connection.getAccountInfo(offerPda));
+    });
+
+    it("Lets the owner accept the offer without listing", async () => {
+      await makeOffer();
+
+      const feeVaultBefore = await connection.getBalance(feeVault);
+
+      await program.methods
+        .acceptOffer()
+        .accounts({
+          seller: lister.publicKey,
+          bidder: buyer.publicKey,
+          bidderAta: getAssociatedTokenAddressSync(offerNft.mint, buyer.publicKey),
+          sellerAta: getAssociatedTokenAddressSync(offerNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          offer: offerPda,
+          bidVault: offerVault,
+          nft: offerNft.mint,
+          metadata: offerNft.metadata,
+          edition: offerNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(offerNft.mint, buyer.publicKey));
+      assert.equal(buyerToken.value.amount, "1");
+      assert.equal(await connection.getBalance(offerVault), 0);
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + offerAmount.toNumber() * marketplace.fee / 10000);
+      assert.isNull(await connection.getAccountInfo(offerPda));
+    });
+  });
+
+  describe("Trait bids", () => {
+    let traitNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let otherNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let root: Buffer;
+    let proof: Buffer[];
+    let traitBidPda: PublicKey;
+    let traitBidVault: PublicKey;
+
+    const bidAmount = new BN(LAMPORTS_PER_SOL / 2);
+
+    // Leaves are keccak(mint) and each level hashes the sorted pair, same as the program
+    const leaf = (mint: PublicKey) => Buffer.from(keccak_256(mint.toBuffer()));
+    const hashPair = (a: Buffer, b: Buffer) => Buffer.from(keccak_256(Buffer.concat(Buffer.compare(a, b) <= 0 ? [a, b] : [b, a])));
+
+    const acceptTraitBid = (nft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey }, nftProof: Buffer[]) => program.methods
+      .acceptTraitBid(nftProof.map((node) => Array.from(node)))
+      .accounts({
+        seller: lister.publicKey,
+        bidder: buyer.publicKey,
+        bidderAta: getAssociatedTokenAddressSync(nft.mint, buyer.publicKey),
+        sellerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+        marketplace: marketplacePda,
+        feeVault,
+        traitBid: traitBidPda,
+        bidVault: traitBidVault,
+        nft: nft.mint,
+        metadata: nft.metadata,
+        edition: nft.edition,
+        sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .remainingAccounts([
+        { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+        { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+      ])
+      .signers([lister]);
+
+    it("Escrows a bid on every mint in the merkle root", async () => {
+      traitNft = await mintVerifiedNft(lister);
+      otherNft = await mintVerifiedNft(lister);
+
+      const eligible = PublicKey.unique();
+      root = hashPair(leaf(traitNft.mint), leaf(eligible));
+      proof = [leaf(eligible)];
+
+      traitBidPda = PublicKey.findProgramAddressSync(
+        [Buffer.from("trait_bid"), marketplacePda.toBuffer(), root, buyer.publicKey.toBuffer()],
+        program.programId
+      )[0];
+      traitBidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), traitBidPda.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .traitBid(Array.from(root), bidAmount, null)
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          traitBid: traitBidPda,
+          bidVault: traitBidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal(await connection.getBalance(traitBidVault), bidAmount.toNumber());
+    });
+
+    it("Rejects a mint that isn't in the root", async () => {
+      try {
+        await acceptTraitBid(otherNft, proof).rpc();
+        assert.fail("mint outside the root should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "InvalidProof");
+      }
+    });
+
+    it("Fills the bid with a proven mint", async () => {
+      await acceptTraitBid(traitNft, proof).rpc().then(confirm).then(log);
+
+      const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(traitNft.mint, buyer.publicKey));
+      assert.equal(buyerToken.value.amount, "1");
+      assert.equal(await connection.getBalance(traitBidVault), 0);
+      assert.isNull(await connection.getAccountInfo(traitBidPda));
+    });
+
+    it("Refunds the bidder when a trait bid is cancelled", async () => {
+      const cancelRoot = hashPair(leaf(otherNft.mint), leaf(PublicKey.unique()));
+      const traitBid = PublicKey.findProgramAddressSync(
+        [Buffer.from("trait_bid"), marketplacePda.toBuffer(), cancelRoot, buyer.publicKey.toBuffer()],
+        program.programId
+      )[0];
+      const bidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), traitBid.toBuffer()], program.programId)[0];
+      const accounts = {
+        bidder: buyer.publicKey,
+        marketplace: marketplacePda,
+        traitBid,
+        bidVault,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      };
+
+      await program.methods
+        .traitBid(Array.from(cancelRoot), bidAmount, null)
+        .accounts(accounts)
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const before = await connection.getBalance(buyer.publicKey);
+
+      await program.methods
+        .cancelTraitBid()
+        .accounts(accounts)
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal(await connection.getBalance(bidVault), 0);
+      assert.isNull(await connection.getAccountInfo(traitBid));
+      // The bidder gets the escrow and the bid account rent back, minus the transaction fee
+      assert.isAbove(await connection.getBalance(buyer.publicKey), before + bidAmount.toNumber() - 10000);
+    });
+  });
+
+
+  describe("English auctions", () => {
+    let auctionNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let auctionPda: PublicKey;
+    let auctionVault: PublicKey;
+    const rival = anchor.web3.Keypair.generate();
+
+    const reservePrice = new BN(LAMPORTS_PER_SOL / 2);
+    const minIncrement = new BN(LAMPORTS_PER_SOL / 10);
+
+    const placeBid = async (bidder: anchor.web3.Keypair, amount: BN, previousBidder: PublicKey | null) => {
+      await program.methods
+        .placeAuctionBid(amount)
+        .accounts({
+          bidder: bidder.publicKey,
+          previousBidder,
+          marketplace: marketplacePda,
+          auction: auctionPda,
+          auctionVault,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([bidder]).rpc().then(confirm).then(log);
+    };
+
+    it("Starts an auction with a reserve price", async () => {
+      auctionNft = await mintVerifiedNft(lister);
+      auctionPda = PublicKey.findProgramAddressSync([Buffer.from("auction"), marketplacePda.toBuffer(), auctionNft.mint.toBuffer()], program.programId)[0];
+      auctionVault = PublicKey.findProgramAddressSync([Buffer.from("auction_vault"), auctionPda.toBuffer()], program.programId)[0];
+      await connection.requestAirdrop(rival.publicKey, 2 * LAMPORTS_PER_SOL).then(confirm);
+
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+
+      await program.methods
+        .createAuction(reservePrice, minIncrement, new BN(now - 1), new BN(now + 3), new BN(2))
+        .accounts({
+          seller: lister.publicKey,
+          sellerAta: getAssociatedTokenAddressSync(auctionNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          auction: auctionPda,
+          collection: collectionMint,
+          nft: auctionNft.mint,
+          metadata: auctionNft.metadata,
+          edition: auctionNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const sellerToken = await getAccount(connection, getAssociatedTokenAddressSync(auctionNft.mint, lister.publicKey));
+      assert.isTrue(sellerToken.isFrozen);
+    });
+
+    it("Rejects a bid under the reserve price", async () => {
+      try {
+        await placeBid(buyer, reservePrice.subn(1), null);
+        assert.fail("bid under the reserve was accepted");
+      } catch (err) {
+        assert.include(err.toString(), "BidTooLow");
+      }
+    });
+
+    it("Refunds the outbid bidder and extends the end time", async () => {
+      await placeBid(buyer, reservePrice, null);
+      const buyerBefore = await connection.getBalance(buyer.publicKey);
+
+      const outbid = reservePrice.add(minIncrement);
+      await placeBid(rival, outbid, buyer.publicKey);
+
+      const auction = await program.account.auction.fetch(auctionPda);
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+      assert.ok(auction.highestBidder.equals(rival.publicKey));
+      assert.isAtLeast(auction.endTime.toNumber(), now + 1);
+      assert.equal(await connection.getBalance(auctionVault), outbid.toNumber());
+      assert.equal(await connection.getBalance(buyer.publicKey), buyerBefore + reservePrice.toNumber());
+    });
+
+    it("Lets anyone settle the auction once it has ended", async () => {
+      await new Promise((resolve) => setTimeout(resolve, 5000));
+
+      const keeper = anchor.web3.Keypair.generate();
+      await connection.requestAirdrop(keeper.publicKey, LAMPORTS_PER_SOL).then(confirm);
+      const feeVaultBefore = await connection.getBalance(feeVault);
+      const winnerAta = getAssociatedTokenAddressSync(auctionNft.mint, rival.publicKey);
+
+      await program.methods
+        .settleAuction()
+        .accounts({
+          payer: keeper.publicKey,
+          seller: lister.publicKey,
+          sellerAta: getAssociatedTokenAddressSync(auctionNft.mint, lister.publicKey),
+          winner: rival.publicKey,
+          winnerAta,
+          marketplace: marketplacePda,
+          feeVault,
+          auction: auctionPda,
+          auctionVault,
+          nft: auctionNft.mint,
+          metadata: auctionNft.metadata,
+          edition: auctionNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([keeper]).rpc().then(confirm).then(log);
+
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const winnerToken = await connection.getTokenAccountBalance(winnerAta);
+      assert.equal(winnerToken.value.amount, "1");
+      assert.equal(await connection.getBalance(auctionVault), 0);
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + reservePrice.add(minIncrement).toNumber() * marketplace.fee / 10000);
+      assert.isNull(await connection.getAccountInfo(auctionPda));
+    });
+
+    // Starts an auction without bids on a fresh NFT, returning the accounts settle and cancel need
+    const startUnbidAuction = async (duration: number) => {
+      const nft = await mintVerifiedNft(lister);
+      const auction = PublicKey.findProgramAddressSync([Buffer.from("auction"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0];
+      const vault = PublicKey.findProgramAddressSync([Buffer.from("auction_vault"), auction.toBuffer()], program.programId)[0];
+      const sellerAta = getAssociatedTokenAddressSync(nft.mint, lister.publicKey);
+
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+
+      await program.methods
+        .createAuction(reservePrice, minIncrement, new BN(now - 1), new BN(now + duration), new BN(0))
+        .accounts({
+          seller: lister.publicKey,
+          sellerAta,
+          marketplace: marketplacePda,
+          auction,
+          collection: collectionMint,
+          nft: nft.mint,
+          metadata: nft.metadata,
+          edition: nft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      return {
+        seller: lister.publicKey,
+        sellerAta,
+        winner: null,
+        winnerAta: null,
+        marketplace: marketplacePda,
+        feeVault,
+        auction,
+        auctionVault: vault,
+        nft: nft.mint,
+        metadata: nft.metadata,
+        edition: nft.edition,
+        sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      };
+    };
+
+    it("Hands the NFT back when an auction ends without bids", async () => {
+      const accounts = await startUnbidAuction(2);
+      await new Promise((resolve) => setTimeout(resolve, 4000));
+
+      await program.methods
+        .settleAuction()
+        .accounts({ ...accounts, payer: buyer.publicKey })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const sellerToken = await getAccount(connection, accounts.sellerAta);
+      assert.equal(sellerToken.amount, BigInt(1));
+      assert.isFalse(sellerToken.isFrozen);
+      assert.isNull(sellerToken.delegate);
+      assert.isNull(await connection.getAccountInfo(accounts.auction));
+    });
+
+    it("Lets only the seller cancel an auction nobody has bid on", async () => {
+      const accounts = await startUnbidAuction(60);
+
+      try {
+        await program.methods
+          .cancelAuction()
+          .accounts({ ...accounts, payer: buyer.publicKey })
+          .signers([buyer]).rpc();
+        assert.fail("a stranger cancelled the auction");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "InvalidSeller");
+      }
+
+      await program.methods
+        .cancelAuction()
+        .accounts({ ...accounts, payer: lister.publicKey })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const sellerToken = await getAccount(connection, accounts.sellerAta);
+      assert.isFalse(sellerToken.isFrozen);
+      assert.isNull(sellerToken.delegate);
+      assert.isNull(await connection.getAccountInfo(accounts.auction));
+    });
+  });
+
+  describe("Dutch listings", () => {
+    let dutchNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let dutchListing: PublicKey;
+
+    const startPrice = new BN(2 * LAMPORTS_PER_SOL);
+    const endPrice = new BN(LAMPORTS_PER_SOL);
+
+    it("Lists an NFT with a linearly decaying price", async () => {
+      dutchNft = await mintVerifiedNft(lister);
+      dutchListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), dutchNft.mint.toBuffer()], program.programId)[0];
+
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+
+      await program.methods
+        .listDutch({
+          startPrice,
+          endPrice,
+          startTime: new BN(now - 50),
+          endTime: new BN(now + 50),
+          decay: { linear: {} },
+        }, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(dutchNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: dutchListing,
+          collection: collectionMint,
+          nft: dutchNft.mint,
+          metadata: dutchNft.metadata,
+          edition: dutchNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const listing = await program.account.listing.fetch(dutchListing);
+      assert.ok(listing.dutchAuction.startPrice.eq(startPrice));
+    });
+
+    it("Buys at the price the curve has decayed to", async () => {
+      const feeVaultBefore = await connection.getBalance(feeVault);
+      const buyerAta = getAssociatedTokenAddressSync(dutchNft.mint, buyer.publicKey);
+
+      await program.methods
+        .buy(startPrice, null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta,
+          listerAta: getAssociatedTokenAddressSync(dutchNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: dutchListing,
+          nft: dutchNft.mint,
+          metadata: dutchNft.metadata,
+          edition: dutchNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      // About halfway down the curve > The fee is somewhere between the fee on the end and start prices
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const fee = await connection.getBalance(feeVault) - feeVaultBefore;
+      assert.isAbove(fee, endPrice.toNumber() * marketplace.fee / 10000);
+      assert.isBelow(fee, startPrice.toNumber() * marketplace.fee / 10000);
+      const buyerToken = await connection.getTokenAccountBalance(buyerAta);
+      assert.equal(buyerToken.value.amount, "1");
+    });
+
+    it("Fixes a Dutch listing at its start price", async () => {
+      const nft = await mintVerifiedNft(lister);
+      const listingPda = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0];
+
+      const slot = await connection.getSlot();
+      const now = await connection.getBlockTime(slot);
+
+      await program.methods
+        .listDutch({
+          startPrice,
+          endPrice,
+          startTime: new BN(now - 50),
+          endTime: new BN(now + 50),
+          decay: { linear: {} },
+        }, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: listingPda,
+          collection: collectionMint,
+          nft: nft.mint,
+          metadata: nft.metadata,
+          edition: nft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .updateListing(startPrice)
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: listingPda,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const listing = await program.account.listing.fetch(listingPda);
+      assert.ok(listing.price.eq(startPrice));
+      assert.isNull(listing.dutchAuction);
+    });
+  });
+
+  describe("Private listings", () => {
+    let privateNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let privateListing: PublicKey;
+    const stranger = anchor.web3.Keypair.generate();
+
+    const buyPrivate = async (signer: anchor.web3.Keypair) => {
+      await program.methods
+        .buy(new BN(LAMPORTS_PER_SOL), null)
+        .accounts({
+          buyer: signer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: getAssociatedTokenAddressSync(privateNft.mint, signer.publicKey),
+          listerAta: getAssociatedTokenAddressSync(privateNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: privateListing,
+          nft: privateNft.mint,
+          metadata: privateNft.metadata,
+          edition: privateNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([signer]).rpc().then(confirm).then(log);
+    };
+
+    it("Lists an NFT reserved for one buyer", async () => {
+      privateNft = await mintVerifiedNft(lister);
+      privateListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), privateNft.mint.toBuffer()], program.programId)[0];
+      await connection.requestAirdrop(stranger.publicKey, 2 * LAMPORTS_PER_SOL).then(confirm);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, null, buyer.publicKey)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(privateNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: privateListing,
+          collection: collectionMint,
+          nft: privateNft.mint,
+          metadata: privateNft.metadata,
+          edition: privateNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const listing = await program.account.listing.fetch(privateListing);
+      assert.ok(listing.reservedBuyer.equals(buyer.publicKey));
+    });
+
+    it("Rejects any other buyer", async () => {
+      try {
+        await buyPrivate(stranger);
+        assert.fail("a stranger bought a reserved listing");
+      } catch (err) {
+        assert.include(err.toString(), "ReservedListing");
+      }
+    });
+
+    it("Lets the reserved buyer settle the deal", async () => {
+      await buyPrivate(buyer);
+
+      const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(privateNft.mint, buyer.publicKey));
+      assert.equal(buyerToken.value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(privateListing));
+    });
+  });
+
+  describe("Buyer price guards", () => {
+    let guardedNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let guardedListing: PublicKey;
+
+    const price = new BN(LAMPORTS_PER_SOL);
+
+    const buyGuarded = async (maxPrice: BN, expectedLister: PublicKey | null) => {
+      await program.methods
+        .buy(maxPrice, expectedLister)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: getAssociatedTokenAddressSync(guardedNft.mint, buyer.publicKey),
+          listerAta: getAssociatedTokenAddressSync(guardedNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: guardedListing,
+          nft: guardedNft.mint,
+          metadata: guardedNft.metadata,
+          edition: guardedNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+    };
+
+    it("Rejects a buy once the price went above the buyer's maximum", async () => {
+      guardedNft = await mintVerifiedNft(lister);
+      guardedListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), guardedNft.mint.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .list(price, null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(guardedNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: guardedListing,
+          collection: collectionMint,
+          nft: guardedNft.mint,
+          metadata: guardedNft.metadata,
+          edition: guardedNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .updateListing(price.muln(2))
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          listing: guardedListing,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      try {
+        await buyGuarded(price, null);
+        assert.fail("bought above the maximum price");
+      } catch (err) {
+        assert.include(err.toString(), "PriceAboveMax");
+      }
+    });
+
+    it("Rejects a listing from another lister", async () => {
+      try {
+        await buyGuarded(price.muln(2), buyer.publicKey);
+        assert.fail("bought from an unexpected lister");
+      } catch (err) {
+        assert.include(err.toString(), "UnexpectedLister");
+      }
+    });
+
+    it("Buys when both guards hold", async () => {
+      await buyGuarded(price.muln(2), lister.publicKey);
+
+      const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(guardedNft.mint, buyer.publicKey));
+      assert.equal(buyerToken.value.amount, "1");
+    });
+  });
+
+  describe("Sweeps", () => {
+    let sweptNfts: { mint: PublicKey, metadata: PublicKey, edition: PublicKey }[];
+    let sweptListings: PublicKey[];
+
+    const price = new BN(LAMPORTS_PER_SOL);
+
+    const sweepAccounts = (nft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey }, listing: PublicKey) => [
+      { pubkey: listing, isSigner: false, isWritable: true },
+      { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+      { pubkey: getAssociatedTokenAddressSync(nft.mint, lister.publicKey), isSigner: false, isWritable: true },
+      { pubkey: getAssociatedTokenAddressSync(nft.mint, buyer.publicKey), isSigner: false, isWritable: true },
+      { pubkey: nft.mint, isSigner: false, isWritable: true },
+      { pubkey: nft.metadata, isSigner: false, isWritable: true },
+      { pubkey: nft.edition, isSigner: false, isWritable: false },
+      // The creators, in the order of the metadata
+      { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+      { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+    ];
+
+    it("Lists a few NFTs of the collection", async () => {
+      sweptNfts = [await mintVerifiedNft(lister), await mintVerifiedNft(lister)];
+      sweptListings = sweptNfts.map((nft) => PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0]);
+
+      for (const [i, nft] of sweptNfts.entries()) {
+        await program.methods
+          .list(price, null, null, null)
+          .accounts({
+            lister: lister.publicKey,
+            listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+            marketplace: marketplacePda,
+            listing: sweptListings[i],
+            collection: collectionMint,
+            nft: nft.mint,
+            metadata: nft.metadata,
+            edition: nft.edition,
+            sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([lister]).rpc().then(confirm).then(log);
+      }
+    });
+
+    it("Skips a listing that was already filled", async () => {
+      const [filled, open] = sweptNfts;
+
+      await program.methods
+        .buy(price, null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta: getAssociatedTokenAddressSync(filled.mint, buyer.publicKey),
+          listerAta: getAssociatedTokenAddressSync(filled.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: sweptListings[0],
+          nft: filled.mint,
+          metadata: filled.metadata,
+          edition: filled.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const feeVaultBefore = await connection.getBalance(feeVault);
+
+      await program.methods
+        .sweep(price.muln(2))
+        .accounts({
+          buyer: buyer.publicKey,
+          marketplace: marketplacePda,
+          feeVault,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          ...sweepAccounts(filled, sweptListings[0]),
+          ...sweepAccounts(open, sweptListings[1]),
+        ])
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      // Only the open listing was paid for
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + price.toNumber() * marketplace.fee / 10000);
+      const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(open.mint, buyer.publicKey));
+      assert.equal(buyerToken.value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(sweptListings[1]));
+    });
+
+    it("Buys listings in order until the budget runs out", async () => {
+      // 1 SOL, then 2 SOL, then 1 SOL, swept with 2.5 SOL: the second doesn't fit once the first is bought
+      const prices = [price, price.muln(2), price];
+      const nfts = [await mintVerifiedNft(lister), await mintVerifiedNft(lister), await mintVerifiedNft(lister)];
+      const listings = nfts.map((nft) => PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0]);
+
+      for (const [i, nft] of nfts.entries()) {
+        await program.methods
+          .list(prices[i], null, null, null)
+          .accounts({
+            lister: lister.publicKey,
+            listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+            marketplace: marketplacePda,
+            listing: listings[i],
+            collection: collectionMint,
+            nft: nft.mint,
+            metadata: nft.metadata,
+            edition: nft.edition,
+            sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([lister]).rpc().then(confirm).then(log);
+      }
+
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      const feeVaultBefore = await connection.getBalance(feeVault);
+      const creator2Before = await connection.getBalance(admin.publicKey);
+
+      await program.methods
+        .sweep(price.muln(5).divn(2))
+        .accounts({
+          buyer: buyer.publicKey,
+          marketplace: marketplacePda,
+          feeVault,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(nfts.flatMap((nft, i) => sweepAccounts(nft, listings[i])))
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      // The first and third listings were bought, 2 SOL of the budget spent
+      const spent = price.toNumber() * 2;
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + spent * marketplace.fee / 10000);
+      // 1% royalties, half of them to the second creator
+      assert.equal(await connection.getBalance(admin.publicKey), creator2Before + spent * 100 / 10000 / 2);
+      for (const i of [0, 2]) {
+        const buyerToken = await connection.getTokenAccountBalance(getAssociatedTokenAddressSync(nfts[i].mint, buyer.publicKey));
+        assert.equal(buyerToken.value.amount, "1");
+        assert.isNull(await connection.getAccountInfo(listings[i]));
+      }
+
+      // The one it couldn't afford is still listed
+      const skipped = await program.account.listing.fetch(listings[1]);
+      assert.ok(skipped.price.eq(prices[1]));
+    });
+  });
+
+  describe("Bulk listings", () => {
+    let bulkNfts: { mint: PublicKey, metadata: PublicKey, edition: PublicKey }[];
+    let bulkListings: PublicKey[];
+
+    const bulkAccounts = () => bulkNfts.flatMap((nft, i) => [
+      { pubkey: getAssociatedTokenAddressSync(nft.mint, lister.publicKey), isSigner: false, isWritable: true },
+      { pubkey: bulkListings[i], isSigner: false, isWritable: true },
+      { pubkey: nft.mint, isSigner: false, isWritable: true },
+      { pubkey: nft.metadata, isSigner: false, isWritable: true },
+      { pubkey: nft.edition, isSigner: false, isWritable: false },
+    ]);
+
+    it("Lists several NFTs with their own prices", async () => {
+      bulkNfts = [await mintVerifiedNft(lister), await mintVerifiedNft(lister)];
+      bulkListings = bulkNfts.map((nft) => PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0]);
+      const prices = [new BN(LAMPORTS_PER_SOL), new BN(2 * LAMPORTS_PER_SOL)];
+
+      await program.methods
+        .listMany(prices, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          collection: collectionMint,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(bulkAccounts())
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      for (const [i, listingPda] of bulkListings.entries()) {
+        const listing = await program.account.listing.fetch(listingPda);
+        assert.ok(listing.price.eq(prices[i]));
+        const listerToken = await getAccount(connection, getAssociatedTokenAddressSync(bulkNfts[i].mint, lister.publicKey));
+        assert.isTrue(listerToken.isFrozen);
+      }
+    });
+
+    it("Delists them all at once", async () => {
+      await program.methods
+        .delistMany()
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(bulkAccounts())
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      for (const [i, listingPda] of bulkListings.entries()) {
+        assert.isNull(await connection.getAccountInfo(listingPda));
+        const listerToken = await getAccount(connection, getAssociatedTokenAddressSync(bulkNfts[i].mint, lister.publicKey));
+        assert.isFalse(listerToken.isFrozen);
+        assert.isNull(listerToken.delegate);
+      }
+    });
+
+    it("Lists over a listing address that was sent lamports beforehand", async () => {
+      await anchor.web3.sendAndConfirmTransaction(
+        connection,
+        new anchor.web3.Transaction().add(anchor.web3.SystemProgram.transfer({
+          fromPubkey: buyer.publicKey,
+          toPubkey: bulkListings[0],
+          lamports: 1_000,
+        })),
+        [buyer]
+      );
+
+      await program.methods
+        .listMany([new BN(LAMPORTS_PER_SOL), new BN(LAMPORTS_PER_SOL)], null, null)
+        .accounts({
+          lister: lister.publicKey,
+          marketplace: marketplacePda,
+          collection: collectionMint,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(bulkAccounts())
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const listingInfo = await connection.getAccountInfo(bulkListings[0]);
+      assert.ok(listingInfo.owner.equals(program.programId));
+      const listing = await program.account.listing.fetch(bulkListings[0]);
+      assert.ok(listing.lister.equals(lister.publicKey));
+    });
+
+    it("Rejects a zero price on a single listing too", async () => {
+      const nft = await mintVerifiedNft(lister);
+
+      try {
+        await program.methods
+          .list(new BN(0), null, null, null)
+          .accounts({
+            lister: lister.publicKey,
+            listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+            marketplace: marketplacePda,
+            listing: PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0],
+            collection: collectionMint,
+            nft: nft.mint,
+            metadata: nft.metadata,
+            edition: nft.edition,
+            sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([lister]).rpc();
+        assert.fail("a zero price listing should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "InvalidAmount");
+      }
+    });
+  });
+
+  describe("Escrowed listings", () => {
+    let escrowNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let escrowListing: PublicKey;
+    let listingVault: PublicKey;
+
+    const setListingMode = async (escrowListings: boolean) => {
+      await program.methods
+        .updateListingMode(escrowListings)
+        .accounts({
+          admin: admin.publicKey,
+          marketplace: marketplacePda,
+        })
+        .signers([admin]).rpc().then(confirm).then(log);
+    };
+
+    it("Moves the NFT into the listing vault when the marketplace escrows listings", async () => {
+      await setListingMode(true);
+
+      escrowNft = await mintVerifiedNft(lister);
+      escrowListing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), escrowNft.mint.toBuffer()], program.programId)[0];
+      listingVault = getAssociatedTokenAddressSync(escrowNft.mint, escrowListing, true);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, null, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(escrowNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing: escrowListing,
+          listingVault,
+          collection: collectionMint,
+          nft: escrowNft.mint,
+          metadata: escrowNft.metadata,
+          edition: escrowNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const listing = await program.account.listing.fetch(escrowListing);
+      assert.isTrue(listing.escrowed);
+      const vaultToken = await connection.getTokenAccountBalance(listingVault);
+      assert.equal(vaultToken.value.amount, "1");
+    });
+
+    it("Buys out of the listing vault", async () => {
+      const buyerAta = getAssociatedTokenAddressSync(escrowNft.mint, buyer.publicKey);
+
+      await program.methods
+        .buy(new BN(LAMPORTS_PER_SOL), null)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: lister.publicKey,
+          buyerAta,
+          listerAta: getAssociatedTokenAddressSync(escrowNft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing: escrowListing,
+          listingVault,
+          nft: escrowNft.mint,
+          metadata: escrowNft.metadata,
+          edition: escrowNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      const buyerToken = await connection.getTokenAccountBalance(buyerAta);
+      assert.equal(buyerToken.value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(listingVault));
+      assert.isNull(await connection.getAccountInfo(escrowListing));
+    });
+
+    const listEscrowed = async (expiresAt: BN | null) => {
+      const nft = await mintVerifiedNft(lister);
+      const listing = PublicKey.findProgramAddressSync([Buffer.from("listing"), marketplacePda.toBuffer(), nft.mint.toBuffer()], program.programId)[0];
+      const vault = getAssociatedTokenAddressSync(nft.mint, listing, true);
+
+      await program.methods
+        .list(new BN(LAMPORTS_PER_SOL), null, expiresAt, null)
+        .accounts({
+          lister: lister.publicKey,
+          listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          listing,
+          listingVault: vault,
+          collection: collectionMint,
+          nft: nft.mint,
+          metadata: nft.metadata,
+          edition: nft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      return { nft, listing, vault };
+    };
+
+    it("Gives the NFT back out of the listing vault on delist", async () => {
+      const { nft, listing, vault } = await listEscrowed(null);
+      const listerAta = getAssociatedTokenAddressSync(nft.mint, lister.publicKey);
+
+      await program.methods
+        .delist()
+        .accounts({
+          lister: lister.publicKey,
+          listerAta,
+          marketplace: marketplacePda,
+          listing,
+          listingVault: vault,
+          nft: nft.mint,
+          metadata: nft.metadata,
+          edition: nft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(listerAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(vault));
+      assert.isNull(await connection.getAccountInfo(listing));
+    });
+
+    it("Fills a bid out of the listing vault", async () => {
+      const { nft, listing, vault } = await listEscrowed(null);
+      const bid = PublicKey.findProgramAddressSync([Buffer.from("bid"), listing.toBuffer(), buyer.publicKey.toBuffer()], program.programId)[0];
+      const bidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), bid.toBuffer()], program.programId)[0];
+      const bidderAta = getAssociatedTokenAddressSync(nft.mint, buyer.publicKey);
+
+      await program.methods
+        .bid(new BN(LAMPORTS_PER_SOL / 2))
+        .accounts({
+          bidder: buyer.publicKey,
+          marketplace: marketplacePda,
+          listing,
+          bid,
+          bidVault,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .acceptBid(new BN(LAMPORTS_PER_SOL / 2))
+        .accounts({
+          lister: lister.publicKey,
+          bidder: buyer.publicKey,
+          bidderAta,
+          listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+          marketplace: marketplacePda,
+          feeVault,
+          listing,
+          listingVault: vault,
+          bid,
+          bidVault,
+          nft: nft.mint,
+          metadata: nft.metadata,
+          edition: nft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(bidderAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(vault));
+      assert.isNull(await connection.getAccountInfo(listing));
+    });
+
+    it("Returns the NFT out of the listing vault when an expired listing is closed", async () => {
+      const now = await connection.getBlockTime(await connection.getSlot());
+      const { nft, listing, vault } = await listEscrowed(new BN(now + 2));
+      const listerAta = getAssociatedTokenAddressSync(nft.mint, lister.publicKey);
+
+      await new Promise((resolve) => setTimeout(resolve, 4000));
+
+      const keeper = anchor.web3.Keypair.generate();
+      await connection.requestAirdrop(keeper.publicKey, LAMPORTS_PER_SOL).then(confirm);
+
+      await program.methods
+        .closeExpiredListing()
+        .accounts({
+          payer: keeper.publicKey,
+          lister: lister.publicKey,
+          listerAta,
+          marketplace: marketplacePda,
+          listing,
+          listingVault: vault,
+          nft: nft.mint,
+          metadata: nft.metadata,
+          edition: nft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([keeper]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(listerAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(vault));
+      assert.isNull(await connection.getAccountInfo(listing));
+    });
+
+    it("Sweeps an escrowed listing through its listing vault", async () => {
+      const { nft, listing, vault } = await listEscrowed(null);
+      const buyerAta = getAssociatedTokenAddressSync(nft.mint, buyer.publicKey);
+
+      await program.methods
+        .sweep(new BN(LAMPORTS_PER_SOL))
+        .accounts({
+          buyer: buyer.publicKey,
+          marketplace: marketplacePda,
+          feeVault,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: listing, isSigner: false, isWritable: true },
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: vault, isSigner: false, isWritable: true },
+          { pubkey: buyerAta, isSigner: false, isWritable: true },
+          { pubkey: nft.mint, isSigner: false, isWritable: true },
+          { pubkey: nft.metadata, isSigner: false, isWritable: true },
+          { pubkey: nft.edition, isSigner: false, isWritable: false },
+          { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 })])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(buyerAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(vault));
+      assert.isNull(await connection.getAccountInfo(listing));
+    });
+
+    it("Rejects another NFT's metadata", async () => {
+      const { nft, listing, vault } = await listEscrowed(null);
+      const other = await mintVerifiedNft(lister);
+
+      try {
+        await program.methods
+          .buy(new BN(LAMPORTS_PER_SOL), null)
+          .accounts({
+            buyer: buyer.publicKey,
+            lister: lister.publicKey,
+            buyerAta: getAssociatedTokenAddressSync(nft.mint, buyer.publicKey),
+            listerAta: getAssociatedTokenAddressSync(nft.mint, lister.publicKey),
+            marketplace: marketplacePda,
+            feeVault,
+            listing,
+            listingVault: vault,
+            nft: nft.mint,
+            metadata: other.metadata,
+            edition: nft.edition,
+            sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+            tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([buyer]).rpc();
+        assert.fail("metadata of another NFT should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "ConstraintSeeds");
+      }
+    });
+
+    it("Turns escrow mode back off", async () => {
+      await setListingMode(false);
+
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      assert.isFalse(marketplace.escrowListings);
+    });
+  });
+
+  describe("Swaps", () => {
+    let makerNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let takerNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let swapPda: PublicKey;
+    let swapVault: PublicKey;
+    let swapBidVault: PublicKey;
+    const swapAmount = new BN(LAMPORTS_PER_SOL / 10);
+
+    const swapAccounts = (seed: BN) => {
+      const swap = PublicKey.findProgramAddressSync([Buffer.from("swap"), marketplacePda.toBuffer(), lister.publicKey.toBuffer(), seed.toArrayLike(Buffer, "le", 8)], program.programId)[0];
+      const bidVault = PublicKey.findProgramAddressSync([Buffer.from("bid_vault"), swap.toBuffer()], program.programId)[0];
+      return { swap, bidVault };
+    };
+
+    it("Escrows the maker's NFT and lamports for a wanted NFT", async () => {
+      makerNft = await mintVerifiedNft(lister);
+      takerNft = await mintVerifiedNft(buyer);
+      const seed = new BN(1);
+      ({ swap: swapPda, bidVault: swapBidVault } = swapAccounts(seed));
+      swapVault = getAssociatedTokenAddressSync(makerNft.mint, swapPda, true);
+
+      await program.methods
+        .createSwap(seed, { nft: { 0: takerNft.mint } }, swapAmount)
+        .accounts({
+          maker: lister.publicKey,
+          marketplace: marketplacePda,
+          swap: swapPda,
+          bidVault: swapBidVault,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: getAssociatedTokenAddressSync(makerNft.mint, lister.publicKey), isSigner: false, isWritable: true },
+          { pubkey: swapVault, isSigner: false, isWritable: true },
+          { pubkey: makerNft.mint, isSigner: false, isWritable: false },
+          { pubkey: makerNft.metadata, isSigner: false, isWritable: true },
+          { pubkey: makerNft.edition, isSigner: false, isWritable: false },
+        ])
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 })])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const swap = await program.account.swapState.fetch(swapPda);
+      assert.equal(swap.offeredNfts.length, 1);
+      assert.ok(swap.offeredNfts[0].equals(makerNft.mint));
+      const vaultToken = await connection.getTokenAccountBalance(swapVault);
+      assert.equal(vaultToken.value.amount, "1");
+      assert.isAtLeast(await connection.getBalance(swapBidVault), swapAmount.toNumber());
+    });
+
+    it("Swaps both sides when the taker hands over the wanted NFT", async () => {
+      const takerBalance = await connection.getBalance(buyer.publicKey);
+      const feeVaultBefore = await connection.getBalance(feeVault);
+      const makerAta = getAssociatedTokenAddressSync(takerNft.mint, lister.publicKey);
+      const takerAta = getAssociatedTokenAddressSync(makerNft.mint, buyer.publicKey);
+
+      await program.methods
+        .acceptSwap()
+        .accounts({
+          taker: buyer.publicKey,
+          maker: lister.publicKey,
+          takerAta: getAssociatedTokenAddressSync(takerNft.mint, buyer.publicKey),
+          makerAta,
+          marketplace: marketplacePda,
+          feeVault,
+          swap: swapPda,
+          bidVault: swapBidVault,
+          nft: takerNft.mint,
+          metadata: takerNft.metadata,
+          edition: takerNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: swapVault, isSigner: false, isWritable: true },
+          { pubkey: takerAta, isSigner: false, isWritable: true },
+          { pubkey: makerNft.mint, isSigner: false, isWritable: false },
+          { pubkey: makerNft.metadata, isSigner: false, isWritable: true },
+          { pubkey: makerNft.edition, isSigner: false, isWritable: false },
+          { pubkey: buyer.publicKey, isSigner: false, isWritable: true },
+          { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+        ])
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 600_000 })])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(makerAta)).value.amount, "1");
+      assert.equal((await connection.getTokenAccountBalance(takerAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(swapVault));
+      assert.isNull(await connection.getAccountInfo(swapPda));
+      assert.isAbove(await connection.getBalance(buyer.publicKey), takerBalance);
+      const marketplace = await program.account.marketplace.fetch(marketplacePda);
+      assert.equal(await connection.getBalance(feeVault), feeVaultBefore + swapAmount.toNumber() * marketplace.fee / 10000);
+    });
+
+    it("Gives everything back to the maker on cancel", async () => {
+      const cancelNft = await mintVerifiedNft(lister);
+      const seed = new BN(2);
+      const { swap, bidVault } = swapAccounts(seed);
+      const vault = getAssociatedTokenAddressSync(cancelNft.mint, swap, true);
+      const makerAta = getAssociatedTokenAddressSync(cancelNft.mint, lister.publicKey);
+      const nftAccounts = (from: PublicKey, to: PublicKey) => [
+        { pubkey: from, isSigner: false, isWritable: true },
+        { pubkey: to, isSigner: false, isWritable: true },
+        { pubkey: cancelNft.mint, isSigner: false, isWritable: false },
+        { pubkey: cancelNft.metadata, isSigner: false, isWritable: true },
+        { pubkey: cancelNft.edition, isSigner: false, isWritable: false },
+      ];
+      const accounts = {
+        maker: lister.publicKey,
+        marketplace: marketplacePda,
+        swap,
+        bidVault,
+        sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+        tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      };
+
+      await program.methods
+        .createSwap(seed, { collection: { 0: collectionMint } }, new BN(0))
+        .accounts(accounts)
+        .remainingAccounts(nftAccounts(makerAta, vault))
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 })])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .cancelSwap()
+        .accounts(accounts)
+        .remainingAccounts(nftAccounts(vault, makerAta))
+        .preInstructions([anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 400_000 })])
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(makerAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(vault));
+      assert.isNull(await connection.getAccountInfo(swap));
+    });
+  });
+
+  describe("Bonding-curve pools", () => {
+    let poolNft: { mint: PublicKey, metadata: PublicKey, edition: PublicKey };
+    let poolPda: PublicKey;
+    let poolVault: PublicKey;
+    const spotPrice = new BN(LAMPORTS_PER_SOL);
+    const delta = new BN(LAMPORTS_PER_SOL / 10);
+
+    const creators = [
+      { pubkey: lister.publicKey, isSigner: false, isWritable: true },
+      { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+    ];
+
+    it("Rejects a linear pool whose first buy would be free", async () => {
+      const seed = new BN(99);
+      const pool = PublicKey.findProgramAddressSync([Buffer.from("pool"), marketplacePda.toBuffer(), lister.publicKey.toBuffer(), seed.toArrayLike(Buffer, "le", 8)], program.programId)[0];
+
+      try {
+        await program.methods
+          .createPool(seed, collectionMint, { token: {} }, { linear: { delta: spotPrice } }, spotPrice, 0)
+          .accounts({
+            owner: lister.publicKey,
+            marketplace: marketplacePda,
+            pool,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([lister]).rpc();
+        assert.fail("pool priced down to zero was created");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "InvalidPoolPrice");
+      }
+    });
+
+    it("Creates a two-sided pool and funds it with SOL and an NFT", async () => {
+      const seed = new BN(1);
+      poolPda = PublicKey.findProgramAddressSync([Buffer.from("pool"), marketplacePda.toBuffer(), lister.publicKey.toBuffer(), seed.toArrayLike(Buffer, "le", 8)], program.programId)[0];
+      poolVault = PublicKey.findProgramAddressSync([Buffer.from("pool_vault"), poolPda.toBuffer()], program.programId)[0];
+      poolNft = await mintVerifiedNft(lister);
+
+      await program.methods
+        .createPool(seed, collectionMint, { trade: {} }, { linear: { delta } }, spotPrice, 0)
+        .accounts({
+          owner: lister.publicKey,
+          marketplace: marketplacePda,
+          pool: poolPda,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .depositPoolSol(new BN(2 * LAMPORTS_PER_SOL))
+        .accounts({
+          owner: lister.publicKey,
+          marketplace: marketplacePda,
+          pool: poolPda,
+          poolVault,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .depositPoolNft()
+        .accounts({
+          owner: lister.publicKey,
+          ownerAta: getAssociatedTokenAddressSync(poolNft.mint, lister.publicKey),
+          poolAta: getAssociatedTokenAddressSync(poolNft.mint, poolPda, true),
+          marketplace: marketplacePda,
+          pool: poolPda,
+          nft: poolNft.mint,
+          metadata: poolNft.metadata,
+          edition: poolNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      const pool = await program.account.pool.fetch(poolPda);
+      assert.equal(pool.nftCount, 1);
+      assert.ok(pool.spotPrice.eq(spotPrice));
+      assert.equal(await connection.getBalance(poolVault), 2 * LAMPORTS_PER_SOL);
+    });
+
+    it("Sells the NFT at the spot price and moves the curve up", async () => {
+      const buyerAta = getAssociatedTokenAddressSync(poolNft.mint, buyer.publicKey);
+
+      await program.methods
+        .buyFromPool(spotPrice)
+        .accounts({
+          buyer: buyer.publicKey,
+          owner: lister.publicKey,
+          buyerAta,
+          poolAta: getAssociatedTokenAddressSync(poolNft.mint, poolPda, true),
+          marketplace: marketplacePda,
+          feeVault,
+          pool: poolPda,
+          poolVault,
+          nft: poolNft.mint,
+          metadata: poolNft.metadata,
+          edition: poolNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(creators)
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(buyerAta)).value.amount, "1");
+      const pool = await program.account.pool.fetch(poolPda);
+      assert.equal(pool.nftCount, 0);
+      assert.ok(pool.spotPrice.eq(spotPrice.add(delta)));
+    });
+
+    it("Buys the NFT back one step below the spot price", async () => {
+      const sellerBalance = await connection.getBalance(buyer.publicKey);
+
+      await program.methods
+        .sellToPool(spotPrice)
+        .accounts({
+          seller: buyer.publicKey,
+          owner: lister.publicKey,
+          sellerAta: getAssociatedTokenAddressSync(poolNft.mint, buyer.publicKey),
+          poolAta: getAssociatedTokenAddressSync(poolNft.mint, poolPda, true),
+          marketplace: marketplacePda,
+          feeVault,
+          pool: poolPda,
+          poolVault,
+          nft: poolNft.mint,
+          metadata: poolNft.metadata,
+          edition: poolNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts(creators)
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.isAbove(await connection.getBalance(buyer.publicKey), sellerBalance);
+      const pool = await program.account.pool.fetch(poolPda);
+      assert.equal(pool.nftCount, 1);
+      assert.ok(pool.spotPrice.eq(spotPrice));
+    });
+
+    it("Lets the owner withdraw the NFT and the SOL", async () => {
+      const ownerAta = getAssociatedTokenAddressSync(poolNft.mint, lister.publicKey);
+
+      await program.methods
+        .withdrawPoolNft()
+        .accounts({
+          owner: lister.publicKey,
+          ownerAta,
+          poolAta: getAssociatedTokenAddressSync(poolNft.mint, poolPda, true),
+          marketplace: marketplacePda,
+          pool: poolPda,
+          nft: poolNft.mint,
+          metadata: poolNft.metadata,
+          edition: poolNft.edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      await program.methods
+        .withdrawPoolSol(null)
+        .accounts({
+          owner: lister.publicKey,
+          marketplace: marketplacePda,
+          pool: poolPda,
+          poolVault,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([lister]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(ownerAta)).value.amount, "1");
+      assert.equal(await connection.getBalance(poolVault), 0);
+    });
+  });
+
+  describe("Legacy marketplaces", () => {
+    // tests/fixtures/legacy_marketplace.json is loaded at genesis with the original
+    // `{ admin, fee, name }` layout, created by this deterministic admin
+    const legacyName = "Legacy Marketplace";
+    const legacyAdmin = anchor.web3.Keypair.fromSeed(new Uint8Array(32).fill(1));
+    const legacyMarketplacePda = PublicKey.findProgramAddressSync([Buffer.from("marketplace"), Buffer.from(legacyName), legacyAdmin.publicKey.toBuffer()], program.programId)[0];
+
+    it("Migrates a marketplace to the current layout", async () => {
+      await connection.requestAirdrop(legacyAdmin.publicKey, LAMPORTS_PER_SOL * 10).then(confirm);
+
+      const legacyInfo = await connection.getAccountInfo(legacyMarketplacePda);
+      assert.equal(legacyInfo.data.length, 8 + 32 + 2 + 4 + legacyName.length);
+
+      await program.methods
+        .migrateMarketplace(legacyName)
+        .accounts({
+          admin: legacyAdmin.publicKey,
+          marketplace: legacyMarketplacePda,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([legacyAdmin]).rpc().then(confirm).then(log);
+
+      const marketplace = await program.account.marketplace.fetch(legacyMarketplacePda);
+      assert.ok(marketplace.admin.equals(legacyAdmin.publicKey));
+      assert.ok(marketplace.creator.equals(legacyAdmin.publicKey));
+      assert.isNull(marketplace.pendingAdmin);
+      assert.equal(marketplace.fee, 500);
+      assert.equal(marketplace.name, legacyName);
+      assert.isEmpty(marketplace.paymentMints);
+      assert.isFalse(marketplace.escrowListings);
+    });
+
+    it("Rejects migrating a marketplace twice", async () => {
+      try {
+        await program.methods
+          .migrateMarketplace(legacyName)
+          .accounts({
+            admin: legacyAdmin.publicKey,
+            marketplace: legacyMarketplacePda,
+            systemProgram: anchor.web3.SystemProgram.programId,
+          })
+          .signers([legacyAdmin]).rpc();
+        assert.fail("a migrated marketplace should be rejected");
+      } catch (e) {
+        assert.equal((e as AnchorError).error.errorCode.code, "MarketplaceAlreadyMigrated");
+      }
+    });
+
+    it("Migrates a legacy listing and buys it", async () => {
+      // tests/fixtures/legacy_listing.json sits at the `[b"listing", marketplace]` PDA of the
+      // legacy marketplace and lists this lister's mint for 1 SOL
+      const legacyLister = anchor.web3.Keypair.fromSeed(new Uint8Array(32).fill(2));
+      const legacyNft = anchor.web3.Keypair.fromSeed(new Uint8Array(32).fill(3));
+      const legacyListingPda = PublicKey.findProgramAddressSync([Buffer.from("listing"), legacyMarketplacePda.toBuffer()], program.programId)[0];
+      const legacyFeeVault = PublicKey.findProgramAddressSync([Buffer.from("fee_vault"), legacyMarketplacePda.toBuffer()], program.programId)[0];
+      const price = LAMPORTS_PER_SOL;
+
+      await connection.requestAirdrop(legacyLister.publicKey, LAMPORTS_PER_SOL * 10).then(confirm);
+
+      const umi = createUmi(connection.rpcEndpoint);
+      const listerSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(legacyLister.secretKey));
+      const mintSigner = createSignerFromKeypair(umi, umi.eddsa.createKeypairFromSecretKey(legacyNft.secretKey));
+      umi.use(signerIdentity(listerSigner));
+      umi.use(mplTokenMetadata())
+
+      await createNft(
+        umi,
+        {
+          mint: mintSigner,
+          authority: listerSigner,
+          updateAuthority: listerSigner.publicKey,
+          name: "NFT Example",
+          symbol: "EXM",
+          uri: "",
+          sellerFeeBasisPoints: percentAmount(1),
+          creators: [
+              {address: listerSigner.publicKey, verified: true, share: 100 },
+          ],
+          collection: null,
+          uses: null,
+          isMutable: true,
+          collectionDetails: null,
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      // The legacy program delegated to the old listing PDA before locking, which
+      // can't be replayed here since only the program can sign for that PDA
+      await delegateStandardV1(
+        umi,
+        {
+          mint: publicKey(legacyNft.publicKey),
+          tokenOwner: listerSigner.publicKey,
+          authority: listerSigner,
+          delegate: publicKey(legacyListingPda),
+          tokenStandard: TokenStandard.NonFungible,
+        }
+      ).sendAndConfirm(umi, { send: { skipPreflight: true }, confirm: { commitment: 'confirmed' } });
+
+      const nft = legacyNft.publicKey;
+      const metadata_seeds = [
+        Buffer.from('metadata'),
+        new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID).toBuffer(),
+        nft.toBuffer(),
+      ];
+      const metadata = PublicKey.findProgramAddressSync(metadata_seeds, new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID))[0];
+      const edition = PublicKey.findProgramAddressSync([...metadata_seeds, Buffer.from("edition")], new PublicKey(MPL_TOKEN_METADATA_PROGRAM_ID))[0];
+      const legacyListerAta = getAssociatedTokenAddressSync(nft, legacyLister.publicKey);
+      const listing = PublicKey.findProgramAddressSync([Buffer.from("listing"), legacyMarketplacePda.toBuffer(), nft.toBuffer()], program.programId)[0];
+
+      await program.methods
+        .migrateListing()
+        .accounts({
+          lister: legacyLister.publicKey,
+          listerAta: legacyListerAta,
+          marketplace: legacyMarketplacePda,
+          legacyListing: legacyListingPda,
+          listing,
+          nft,
+          metadata,
+          edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([legacyLister]).rpc().then(confirm).then(log);
+
+      assert.isNull(await connection.getAccountInfo(legacyListingPda));
+      const migrated = await program.account.listing.fetch(listing);
+      assert.ok(migrated.lister.equals(legacyLister.publicKey));
+      assert.ok(migrated.price.eq(new BN(price)));
+      assert.isTrue((await getAccount(connection, legacyListerAta)).isFrozen);
+
+      const buyerLegacyAta = getAssociatedTokenAddressSync(nft, buyer.publicKey);
+
+      await program.methods
+        .buy(new BN(price), legacyLister.publicKey)
+        .accounts({
+          buyer: buyer.publicKey,
+          lister: legacyLister.publicKey,
+          buyerAta: buyerLegacyAta,
+          listerAta: legacyListerAta,
+          marketplace: legacyMarketplacePda,
+          feeVault: legacyFeeVault,
+          listing,
+          nft,
+          metadata,
+          edition,
+          sysvarInstruction: SYSVAR_INSTRUCTIONS_PUBKEY,
+          tokenMetadataProgram: MPL_TOKEN_METADATA_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .remainingAccounts([
+          { pubkey: legacyLister.publicKey, isSigner: false, isWritable: true },
+        ])
+        .signers([buyer]).rpc().then(confirm).then(log);
+
+      assert.equal((await connection.getTokenAccountBalance(buyerLegacyAta)).value.amount, "1");
+      assert.isNull(await connection.getAccountInfo(listing));
+    });
+  });
+});